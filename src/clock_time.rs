@@ -0,0 +1,257 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, LocalResult, Offset, TimeZone as _};
+use chrono_tz::Tz;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+/// Where a clock time is read, either a named zone like "America/New_York"
+/// that follows its daylight saving changes or a fixed offset from UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeZone {
+    Named(Tz),
+    /// Offset from UTC in seconds.
+    Fixed(i32),
+}
+
+impl TimeZone {
+    /// Reads a zone name like "Europe/London", in any case, or a UTC offset
+    /// like "-05:00".
+    pub fn parse(input: &str) -> Option<TimeZone> {
+        match Tz::from_str_insensitive(input.trim()) {
+            Ok(tz) => Some(TimeZone::Named(tz)),
+            Err(_) => parse_utc_offset(input).map(TimeZone::Fixed),
+        }
+    }
+
+    /// The zone's offset from UTC in seconds at a Unix timestamp.
+    fn offset_at(self, timestamp: i64) -> i32 {
+        match self {
+            TimeZone::Named(tz) => DateTime::from_timestamp(timestamp, 0)
+                .map(|utc| {
+                    tz.offset_from_utc_datetime(&utc.naive_utc())
+                        .fix()
+                        .local_minus_utc()
+                })
+                .unwrap_or_default(),
+            TimeZone::Fixed(utc_offset_secs) => utc_offset_secs,
+        }
+    }
+
+    /// The Unix timestamp when the zone's wall clock reads `local`, counted
+    /// in seconds from midnight on 1 Jan 1970 on that clock. A time skipped
+    /// when the clocks go forward is read with the offset from before.
+    fn to_utc(self, local: i64) -> i64 {
+        let TimeZone::Named(tz) = self else {
+            return local - self.offset_at(local) as i64;
+        };
+        let local_result = DateTime::from_timestamp(local, 0)
+            .map(|local| tz.from_local_datetime(&local.naive_utc()));
+        match local_result {
+            Some(LocalResult::Single(utc) | LocalResult::Ambiguous(utc, _)) => utc.timestamp(),
+            _ => local - self.offset_at(local - DAY_SECS) as i64,
+        }
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> TimeZone {
+        TimeZone::Fixed(0)
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeZone::Named(tz) => f.write_str(tz.name()),
+            TimeZone::Fixed(utc_offset_secs) => f.write_str(&format_utc_offset(*utc_offset_secs)),
+        }
+    }
+}
+
+enum Day {
+    Today,
+    Tomorrow,
+    /// Days since Sunday.
+    Weekday(i64),
+}
+
+/// Works out the Unix timestamp for a clock time like "9pm", "tomorrow 20:30"
+/// or "fri 7:15pm", read in the given time zone. Without a day it means the
+/// next time the clock reads that. Returns None if it can't be read or has
+/// already passed.
+pub fn resolve_clock_time(input: &str, now: u64, time_zone: TimeZone) -> Option<u64> {
+    let input = input.trim().to_lowercase();
+    let mut day = None;
+    let mut time_of_day = String::new();
+    for word in input.split_whitespace() {
+        match word {
+            "today" | "tonight" => day = Some(Day::Today),
+            "tomorrow" | "tmrw" => day = Some(Day::Tomorrow),
+            _ => match parse_weekday(word) {
+                Some(weekday) => day = Some(Day::Weekday(weekday)),
+                None => time_of_day.push_str(word),
+            },
+        }
+    }
+    let (hour, minute) = parse_time_of_day(&time_of_day)?;
+
+    let local_now = now as i64 + time_zone.offset_at(now as i64) as i64;
+    let today_start = local_now - local_now.rem_euclid(DAY_SECS);
+    let mut local_target = today_start + hour as i64 * 60 * 60 + minute as i64 * 60;
+    match day {
+        None if local_target <= local_now => local_target += DAY_SECS,
+        None | Some(Day::Today) => {}
+        Some(Day::Tomorrow) => local_target += DAY_SECS,
+        Some(Day::Weekday(weekday)) => {
+            // The Unix epoch was a Thursday.
+            let today = (today_start / DAY_SECS + 4).rem_euclid(7);
+            local_target += (weekday - today).rem_euclid(7) * DAY_SECS;
+            if local_target <= local_now {
+                local_target += 7 * DAY_SECS;
+            }
+        }
+    }
+
+    let target = time_zone.to_utc(local_target);
+    if target <= now as i64 {
+        return None;
+    }
+    Some(target as u64)
+}
+
+/// Reads "monday", "mon", "thurs" and so on as days since Sunday.
+pub fn parse_weekday(word: &str) -> Option<i64> {
+    let word = word.trim().to_lowercase();
+    if word.len() < 3 {
+        return None;
+    }
+    WEEKDAYS
+        .iter()
+        .position(|weekday| weekday.starts_with(&word))
+        .map(|weekday| weekday as i64)
+}
+
+/// Reads "9pm", "9:30pm", "20:30", "noon" and the like as an hour and minute.
+fn parse_time_of_day(input: &str) -> Option<(u32, u32)> {
+    match input {
+        "noon" => return Some((12, 0)),
+        "midnight" => return Some((0, 0)),
+        _ => {}
+    }
+
+    let (clock, pm) = if let Some(clock) = input.strip_suffix("pm") {
+        (clock, Some(true))
+    } else if let Some(clock) = input.strip_suffix("am") {
+        (clock, Some(false))
+    } else {
+        (input, None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    if minute > 59 {
+        return None;
+    }
+    match pm {
+        Some(pm) if (1..=12).contains(&hour) => Some((hour % 12 + if pm { 12 } else { 0 }, minute)),
+        None if hour < 24 => Some((hour, minute)),
+        _ => None,
+    }
+}
+
+/// Reads a UTC offset like "-05:00", "+1", "UTC+5:30" or "GMT" as seconds.
+pub fn parse_utc_offset(input: &str) -> Option<i32> {
+    let input = input.trim().to_lowercase();
+    let offset = input
+        .strip_prefix("utc")
+        .or_else(|| input.strip_prefix("gmt"))
+        .unwrap_or(&input)
+        .trim();
+    if offset.is_empty() {
+        return Some(0);
+    }
+
+    let (sign, offset) = match offset.strip_prefix('-') {
+        Some(offset) => (-1, offset),
+        None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+    };
+    let (hours, minutes) = match offset.split_once(':') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?),
+        None => (offset.parse::<i32>().ok()?, 0),
+    };
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 * 60 + minutes * 60))
+}
+
+/// Formats an offset in seconds the way `/timezone` shows it, e.g. "UTC-05:00".
+pub fn format_utc_offset(utc_offset_secs: i32) -> String {
+    let sign = if utc_offset_secs < 0 { '-' } else { '+' };
+    let minutes = utc_offset_secs.abs() / 60;
+    format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// The wall clock time in the zone, as "HH:MM".
+pub fn format_local_time(now: u64, time_zone: TimeZone) -> String {
+    let local_now = now as i64 + time_zone.offset_at(now as i64) as i64;
+    let minutes = local_now.rem_euclid(DAY_SECS) / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time Went Backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Friday 1 March 2024, 12:00 UTC.
+    const MARCH_1: u64 = 1_709_294_400;
+    const MARCH_15: u64 = MARCH_1 + 14 * DAY_SECS as u64;
+
+    #[test]
+    fn named_zones_follow_daylight_saving() {
+        let new_york = TimeZone::parse("America/New_York").unwrap();
+        assert_eq!(TimeZone::parse("america/new_york"), Some(new_york));
+        assert_eq!(new_york.to_string(), "America/New_York");
+        // 8pm EST, before the clocks go forward on 10 March.
+        assert_eq!(
+            resolve_clock_time("fri 8pm", MARCH_1, new_york),
+            Some(MARCH_1 + 13 * 60 * 60)
+        );
+        // 8pm EDT two weeks later, an hour earlier in UTC.
+        assert_eq!(
+            resolve_clock_time("fri 8pm", MARCH_15, new_york),
+            Some(MARCH_15 + 12 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn fixed_offsets_stay_put() {
+        let eastern = TimeZone::parse("-05:00").unwrap();
+        assert_eq!(eastern, TimeZone::Fixed(-5 * 60 * 60));
+        assert_eq!(
+            resolve_clock_time("fri 8pm", MARCH_15, eastern),
+            Some(MARCH_15 + 13 * 60 * 60)
+        );
+        assert_eq!(format_local_time(MARCH_1, eastern), "07:00");
+        assert_eq!(eastern.to_string(), "UTC-05:00");
+    }
+}
@@ -0,0 +1,96 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::match_results::{player_records, MatchResultsStore};
+use crate::settings::Settings;
+
+/// How many players the leaderboard lists.
+const LEADERBOARD_SIZE: usize = 15;
+
+pub struct LeaderboardCommand {
+    settings: Settings,
+    match_results: MatchResultsStore,
+}
+
+impl LeaderboardCommand {
+    pub async fn run(
+        &self,
+        ctx: &Context,
+        leaderboard_command: &CommandInteraction,
+    ) -> Result<(), serenity::Error> {
+        let game_name = leaderboard_command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "game")
+            .and_then(|option| option.value.as_str())
+            .unwrap_or_default();
+
+        let content = match leaderboard_command.guild_id {
+            Some(guild_id) => {
+                let records = self
+                    .match_results
+                    .read(|match_results| player_records(match_results, game_name, guild_id));
+                let mut leaderboard = MessageBuilder::new();
+                if records.is_empty() {
+                    leaderboard.push(format!("No {} results have been reported yet", game_name));
+                } else {
+                    leaderboard.push_line(format!("## {} Leaderboard", game_name));
+                }
+                records
+                    .iter()
+                    .take(LEADERBOARD_SIZE)
+                    .enumerate()
+                    .for_each(|(position, record)| {
+                        leaderboard
+                            .push(format!("{}. ", position + 1))
+                            .mention(&record.user_id)
+                            .push(format!(
+                                " - {}% wins over {} games",
+                                record.win_rate_percent(),
+                                record.games
+                            ));
+                        if record.mvps > 0 {
+                            leaderboard.push(format!(", {} MVP", record.mvps));
+                        }
+                        leaderboard.push_line("");
+                    });
+                leaderboard.build()
+            }
+            None => "Leaderboards only work in a server".to_string(),
+        };
+        leaderboard_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await
+    }
+
+    pub fn new(settings: Settings, match_results: MatchResultsStore) -> LeaderboardCommand {
+        LeaderboardCommand {
+            settings,
+            match_results,
+        }
+    }
+
+    pub fn register(&self) -> CreateCommand {
+        let game_option = self.settings.game_queue.iter().fold(
+            CreateCommandOption::new(CommandOptionType::String, "game", "The game to rank")
+                .required(true),
+            |game_option, game_queue| {
+                game_option.add_string_choice(&game_queue.game_name, &game_queue.game_name)
+            },
+        );
+        CreateCommand::new("leaderboard")
+            .description("See who wins the most games out of queues")
+            .dm_permission(false)
+            .add_option(game_option)
+    }
+}
@@ -1 +1,4 @@
+pub mod leaderboard;
+pub mod notifications;
 pub mod queue;
+pub mod timezone;
@@ -0,0 +1,65 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::user_settings::UserSettingsStore;
+
+pub struct NotificationsCommand {
+    user_settings: UserSettingsStore,
+}
+
+impl NotificationsCommand {
+    pub async fn run(
+        &self,
+        ctx: &Context,
+        notifications_command: &CommandInteraction,
+    ) -> Result<(), serenity::Error> {
+        let queue_dms = notifications_command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "queue_dms")
+            .and_then(|option| option.value.as_bool())
+            .unwrap_or(true);
+
+        self.user_settings.update(|user_settings| {
+            user_settings
+                .entry(notifications_command.user.id)
+                .or_default()
+                .no_queue_dms = !queue_dms;
+        });
+
+        let content = if queue_dms {
+            "You'll get a DM when a game you're waiting on is starting"
+        } else {
+            "You'll be pinged in the queue channel instead of getting DMs"
+        };
+        notifications_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(content),
+                ),
+            )
+            .await
+    }
+
+    pub fn new(user_settings: UserSettingsStore) -> NotificationsCommand {
+        NotificationsCommand { user_settings }
+    }
+
+    pub fn register(&self) -> CreateCommand {
+        CreateCommand::new("notifications")
+            .description("Choose how the bot lets you know your next game is starting")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "queue_dms",
+                    "DM me when a game I'm waiting on starts",
+                )
+                .required(true),
+            )
+    }
+}
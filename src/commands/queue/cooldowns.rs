@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use serenity::model::prelude::*;
+
+use crate::settings::QueueCooldowns;
+
+/// A role along with the unix timestamp it can next be pinged at.
+pub type RoleReadyAt = (RoleId, u64);
+
+/// When each user, role and channel last had a queue ping go out.
+#[derive(Default)]
+pub struct PingCooldowns {
+    last_user_ping: HashMap<UserId, u64>,
+    last_role_ping: HashMap<RoleId, u64>,
+    last_channel_ping: HashMap<ChannelId, u64>,
+}
+
+impl PingCooldowns {
+    /// Splits `roles` into the roles that can be pinged now and the roles
+    /// still cooling down, along with when each of those frees up. The pings
+    /// that are let through are recorded.
+    pub fn take_pings(
+        &mut self,
+        cooldowns: &QueueCooldowns,
+        user_id: UserId,
+        channel_id: ChannelId,
+        roles: &[RoleId],
+        now: u64,
+    ) -> (Vec<RoleId>, Vec<RoleReadyAt>) {
+        let ready_at = |last_ping: Option<&u64>, cooldown_secs: u64| {
+            last_ping.map_or(0, |last_ping| last_ping + cooldown_secs)
+        };
+        let user_ready_at = ready_at(self.last_user_ping.get(&user_id), cooldowns.user.as_secs());
+        let channel_ready_at = ready_at(
+            self.last_channel_ping.get(&channel_id),
+            cooldowns.channel.as_secs(),
+        );
+
+        let (allowed, suppressed): (Vec<RoleReadyAt>, Vec<RoleReadyAt>) = roles
+            .iter()
+            .map(|role_id| {
+                let role_ready_at =
+                    ready_at(self.last_role_ping.get(role_id), cooldowns.role.as_secs());
+                (
+                    *role_id,
+                    user_ready_at.max(channel_ready_at).max(role_ready_at),
+                )
+            })
+            .partition(|(_, ready_at)| *ready_at <= now);
+
+        if !allowed.is_empty() {
+            self.last_user_ping.insert(user_id, now);
+            self.last_channel_ping.insert(channel_id, now);
+            allowed.iter().for_each(|(role_id, _)| {
+                self.last_role_ping.insert(*role_id, now);
+            });
+        }
+        (
+            allowed.into_iter().map(|(role_id, _)| role_id).collect(),
+            suppressed,
+        )
+    }
+}
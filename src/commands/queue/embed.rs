@@ -0,0 +1,165 @@
+use serenity::builder::{CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter};
+use serenity::model::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use super::state::{QueuePhase, QueueState};
+use crate::settings::ReactionRole;
+
+/// The queue message while it's running: what's being played, when, and
+/// who's in.
+pub fn build_queue_embed(queue: &QueueState, game_roles: &[ReactionRole]) -> CreateEmbed {
+    let mut queue_embed = build_queue_embed_header(queue, game_roles);
+    queue_embed = match queue.phase {
+        QueuePhase::Countdown => queue_embed.colour(Colour::BLURPLE).field(
+            "Queue Starts",
+            format!("<t:{0}:R>\n<t:{0}:F>", queue.queue_at),
+            true,
+        ),
+        QueuePhase::Open => queue_embed
+            .colour(Colour::DARK_GREEN)
+            .field(
+                "Started Queueing",
+                format!("<t:{}:R>", queue.queue_at),
+                true,
+            )
+            .field(
+                "Approx. Next Match",
+                format!("<t:{0}:R>\n<t:{0}:t>", queue.match_at),
+                true,
+            ),
+    };
+    if let Some(voice_channel_id) = queue.voice_channel_id {
+        queue_embed = queue_embed.field(
+            "Voice Channel",
+            voice_channel_id.mention().to_string(),
+            true,
+        );
+    }
+    if let (QueuePhase::Countdown, Some(guild_id), Some(event_id)) =
+        (queue.phase, queue.guild_id, queue.scheduled_event_id)
+    {
+        queue_embed = queue_embed.field(
+            "Event",
+            format!(
+                "[Mark yourself interested](https://discord.com/events/{}/{})",
+                guild_id, event_id
+            ),
+            true,
+        );
+    }
+
+    let roster_title = match queue.phase {
+        QueuePhase::Countdown => "Joined",
+        QueuePhase::Open => "Waiting For Next Game",
+    };
+    let roster_title = match queue.party_size {
+        Some(party_size) => format!(
+            "{} {}/{}",
+            roster_title,
+            queue.users_waiting.len(),
+            party_size
+        ),
+        None => roster_title.to_string(),
+    };
+    queue_embed = queue_embed.field(
+        roster_title,
+        build_roster(&queue.users_waiting, false),
+        false,
+    );
+    if !queue.waitlist.is_empty() {
+        queue_embed = queue_embed.field("Waitlist", build_roster(&queue.waitlist, true), false);
+    }
+    if queue.ready_check_running {
+        queue_embed = queue_embed.footer(CreateEmbedFooter::new("Ready check running"));
+    }
+    queue_embed
+}
+
+/// What's left of the queue message once the queue is over.
+pub fn build_closed_queue_embed(
+    queue: &QueueState,
+    game_roles: &[ReactionRole],
+    cancelled: bool,
+) -> CreateEmbed {
+    let closed_embed = build_queue_embed_header(queue, game_roles).colour(Colour::DARK_GREY);
+    if cancelled {
+        closed_embed.footer(CreateEmbedFooter::new("Queue Cancelled"))
+    } else {
+        closed_embed.field(
+            "Started Queueing",
+            format!("<t:{}:R>", queue.queue_at),
+            true,
+        )
+    }
+}
+
+/// The role mentions that go in the message content, the only place a
+/// queue pings from.
+pub fn build_queue_pings(queue: &QueueState) -> String {
+    let mut queue_pings = MessageBuilder::new();
+    queue.roles.iter().for_each(|role_id| {
+        queue_pings.mention(role_id).push(" ");
+    });
+    queue_pings.build()
+}
+
+fn build_queue_embed_header(queue: &QueueState, game_roles: &[ReactionRole]) -> CreateEmbed {
+    let mut owner = CreateEmbedAuthor::new(format!("{} is Queueing", queue.owner_name));
+    if let Some(owner_avatar_url) = &queue.owner_avatar_url {
+        owner = owner.icon_url(owner_avatar_url);
+    }
+
+    // Mentions inside an embed show up as role chips without pinging anyone.
+    let mut role_chips = MessageBuilder::new();
+    role_chips.push_line("Looking to Play with");
+    queue
+        .roles
+        .iter()
+        .chain(queue.muted_roles.iter())
+        .for_each(|role_id| {
+            if let Some(emoji_char) = game_roles
+                .iter()
+                .find(|reaction_role| reaction_role.role_id == *role_id)
+                .and_then(|reaction_role| reaction_role.emoji_char.clone())
+            {
+                role_chips.push(emoji_char).push(" ");
+            }
+            role_chips.mention(role_id).push_line("");
+        });
+
+    CreateEmbed::new()
+        .author(owner)
+        .title(format!("{} Queue", queue.game_name))
+        .description(role_chips.build())
+}
+
+fn build_roster(users: &[UserId], numbered: bool) -> String {
+    if users.is_empty() {
+        return "Nobody yet".to_string();
+    }
+    let mut roster = MessageBuilder::new();
+    users.iter().enumerate().for_each(|(position, user_id)| {
+        if numbered {
+            roster.push(format!("{}. ", position + 1));
+        }
+        roster.mention(user_id).push_line("");
+    });
+    roster.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_roster_says_so() {
+        assert_eq!(build_roster(&[], false), "Nobody yet");
+    }
+
+    #[test]
+    fn waitlist_roster_is_numbered() {
+        let users = [UserId::new(1), UserId::new(2)];
+        assert_eq!(build_roster(&users, false), "<@1>\n<@2>\n");
+        assert_eq!(build_roster(&users, true), "1. <@1>\n2. <@2>\n");
+    }
+}
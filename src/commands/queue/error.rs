@@ -0,0 +1,108 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use serenity::http::HttpError;
+use tokio::time::sleep;
+
+/// Discord's error code for a message that no longer exists.
+const UNKNOWN_MESSAGE: isize = 10008;
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug)]
+pub enum QueueError {
+    /// Something the user asked for that can't be done, explained to them in
+    /// an ephemeral reply.
+    User(String),
+    /// The queue message was deleted, which ends the queue.
+    MessageDeleted,
+    /// Discord had trouble, the request may work if tried again.
+    Transient(serenity::Error),
+    /// Anything else, which is logged and given up on.
+    Fatal(serenity::Error),
+}
+
+impl From<serenity::Error> for QueueError {
+    fn from(why: serenity::Error) -> Self {
+        match &why {
+            serenity::Error::Http(HttpError::UnsuccessfulRequest(error_response)) => {
+                if error_response.error.code == UNKNOWN_MESSAGE {
+                    QueueError::MessageDeleted
+                } else if error_response.status_code.is_server_error()
+                    || error_response.status_code.as_u16() == 429
+                {
+                    QueueError::Transient(why)
+                } else {
+                    QueueError::Fatal(why)
+                }
+            }
+            serenity::Error::Http(HttpError::Request(_)) => QueueError::Transient(why),
+            _ => QueueError::Fatal(why),
+        }
+    }
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::User(why) => write!(f, "{why}"),
+            QueueError::MessageDeleted => write!(f, "the queue message was deleted"),
+            QueueError::Transient(why) | QueueError::Fatal(why) => write!(f, "{why}"),
+        }
+    }
+}
+
+/// Sends a Discord request that is safe to repeat, like an edit or an
+/// acknowledgement, trying again with a growing wait in between while Discord
+/// is having trouble.
+pub async fn with_retries<T, F, Fut>(request: F) -> Result<T, QueueError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, serenity::Error>>,
+{
+    retry(request, |why| matches!(why, QueueError::Transient(_))).await
+}
+
+/// Sends a request that posts something new. A request that failed without
+/// an answer may still have gone through, so this only tries again when
+/// Discord answered with a server error, rather than posting twice.
+pub async fn send_with_retries<T, F, Fut>(request: F) -> Result<T, QueueError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, serenity::Error>>,
+{
+    retry(request, |why| {
+        matches!(
+            why,
+            QueueError::Transient(serenity::Error::Http(HttpError::UnsuccessfulRequest(
+                error_response
+            ))) if error_response.status_code.is_server_error()
+        )
+    })
+    .await
+}
+
+async fn retry<T, F, Fut>(
+    mut request: F,
+    should_retry: impl Fn(&QueueError) -> bool,
+) -> Result<T, QueueError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, serenity::Error>>,
+{
+    let mut backoff = Duration::from_secs(1);
+    for _ in 1..MAX_ATTEMPTS {
+        match request().await.map_err(QueueError::from) {
+            Err(why) if should_retry(&why) => {
+                println!(
+                    "Discord request failed, retrying in {}s: {why}",
+                    backoff.as_secs()
+                );
+                sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    request().await.map_err(QueueError::from)
+}
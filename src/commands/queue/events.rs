@@ -0,0 +1,92 @@
+use serenity::builder::{CreateScheduledEvent, EditScheduledEvent};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use super::state::{QueuePhase, QueueState};
+
+/// Creates a voice Scheduled Event for the queue, starting when its countdown
+/// runs out.
+pub async fn create_queue_event(
+    ctx: &Context,
+    queue: &QueueState,
+    voice_channel_id: ChannelId,
+) -> Option<ScheduledEventId> {
+    let guild_id = queue.guild_id?;
+    let start_time = Timestamp::from_unix_timestamp(queue.queue_at as i64).ok()?;
+    match guild_id
+        .create_scheduled_event(
+            ctx,
+            CreateScheduledEvent::new(
+                ScheduledEventType::Voice,
+                format!("{}'s {} Queue", queue.owner_name, queue.game_name),
+                start_time,
+            )
+            .channel_id(voice_channel_id)
+            .description(format!(
+                "Mark yourself interested to join the queue {}",
+                queue.message_id.link(queue.channel_id, queue.guild_id)
+            )),
+        )
+        .await
+    {
+        Ok(scheduled_event) => Some(scheduled_event.id),
+        Err(why) => {
+            println!("Cannot create queue event: {why}");
+            None
+        }
+    }
+}
+
+/// Everyone who has marked themselves interested in the queue's event.
+pub async fn interested_users(ctx: &Context, queue: &QueueState) -> Vec<UserId> {
+    let (Some(guild_id), Some(event_id)) = (queue.guild_id, queue.scheduled_event_id) else {
+        return vec![];
+    };
+    match guild_id.scheduled_event_users(ctx, event_id, None).await {
+        Ok(event_users) => event_users
+            .into_iter()
+            .map(|event_user| event_user.user.id)
+            .filter(|user_id| *user_id != queue.owner_id)
+            .collect(),
+        Err(why) => {
+            println!("Cannot read interested users for queue event: {why}");
+            vec![]
+        }
+    }
+}
+
+/// Keeps the event's start time in line with the queue's countdown.
+pub async fn move_queue_event(ctx: &Context, queue: &QueueState) {
+    let Ok(start_time) = Timestamp::from_unix_timestamp(queue.queue_at as i64) else {
+        return;
+    };
+    edit_queue_event(ctx, queue, EditScheduledEvent::new().start_time(start_time)).await;
+}
+
+pub async fn start_queue_event(ctx: &Context, queue: &QueueState) {
+    edit_queue_event(
+        ctx,
+        queue,
+        EditScheduledEvent::new().status(ScheduledEventStatus::Active),
+    )
+    .await;
+}
+
+/// Ends the event along with the queue. Discord only lets events that
+/// haven't started be cancelled, so a running one is completed instead.
+pub async fn end_queue_event(ctx: &Context, queue: &QueueState) {
+    let status = match queue.phase {
+        QueuePhase::Countdown => ScheduledEventStatus::Canceled,
+        QueuePhase::Open => ScheduledEventStatus::Completed,
+    };
+    edit_queue_event(ctx, queue, EditScheduledEvent::new().status(status)).await;
+}
+
+async fn edit_queue_event(ctx: &Context, queue: &QueueState, edit: EditScheduledEvent<'_>) {
+    let (Some(guild_id), Some(event_id)) = (queue.guild_id, queue.scheduled_event_id) else {
+        return;
+    };
+    if let Err(why) = guild_id.edit_scheduled_event(ctx, event_id, edit).await {
+        println!("Cannot update queue event: {why}");
+    }
+}
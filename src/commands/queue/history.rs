@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use super::state::QueueState;
+
+/// A finished queue, kept around for `/queue stats`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueRecord {
+    pub game_name: String,
+    pub owner_id: UserId,
+    pub roles_pinged: Vec<RoleId>,
+    /// Unix timestamp of when the queue was posted.
+    pub started_at: u64,
+    /// Unix timestamp of when the first game started.
+    pub first_game_at: u64,
+    /// Unix timestamp of when the queue closed.
+    pub ended_at: u64,
+    /// How many different people pressed join over the queue's lifetime.
+    pub joined: usize,
+    /// How many of the people who joined had each pinged role.
+    pub role_joins: HashMap<RoleId, usize>,
+    pub cancelled: bool,
+}
+
+impl QueueRecord {
+    pub fn new(
+        queue: &QueueState,
+        role_joins: HashMap<RoleId, usize>,
+        ended_at: u64,
+        cancelled: bool,
+    ) -> Self {
+        QueueRecord {
+            game_name: queue.game_name.clone(),
+            owner_id: queue.owner_id,
+            roles_pinged: queue.roles.clone(),
+            started_at: queue.started_at,
+            first_game_at: queue.first_game_at.unwrap_or(queue.queue_at),
+            ended_at,
+            joined: queue.joined_users.len(),
+            role_joins,
+            cancelled,
+        }
+    }
+
+    pub fn wait_secs(&self) -> u64 {
+        self.first_game_at.saturating_sub(self.started_at)
+    }
+}
+
+/// Renders the `/queue stats` summary for the given records.
+pub fn build_stats_message(records: &[&QueueRecord], now: u64) -> String {
+    let mut message = MessageBuilder::new();
+    if records.is_empty() {
+        message.push("No queues have finished yet");
+        return message.build();
+    }
+
+    let average_wait_mins =
+        records.iter().map(|record| record.wait_secs()).sum::<u64>() / records.len() as u64 / 60;
+    message.push_line(format!(
+        "## {} Queues, {} Minute Average Wait",
+        records.len(),
+        average_wait_mins
+    ));
+
+    let mut queues_by_hour: HashMap<u64, usize> = HashMap::new();
+    records.iter().for_each(|record| {
+        *queues_by_hour
+            .entry((record.started_at / (60 * 60)) % 24)
+            .or_default() += 1;
+    });
+    let today = now - now % (24 * 60 * 60);
+    message.push_line("### Busiest Hours");
+    top_counts(queues_by_hour, 3)
+        .iter()
+        .for_each(|(hour, queue_count)| {
+            message.push_line(format!(
+                "<t:{}:t> - {} queues",
+                today + hour * 60 * 60,
+                queue_count
+            ));
+        });
+
+    let mut queues_by_owner: HashMap<UserId, usize> = HashMap::new();
+    records.iter().for_each(|record| {
+        *queues_by_owner.entry(record.owner_id).or_default() += 1;
+    });
+    message.push_line("### Top Queue Starters");
+    top_counts(queues_by_owner, 5)
+        .iter()
+        .for_each(|(owner_id, queue_count)| {
+            message
+                .mention(owner_id)
+                .push_line(format!(" - {} queues", queue_count));
+        });
+
+    let mut joins_by_role: HashMap<RoleId, (usize, usize)> = HashMap::new();
+    records.iter().for_each(|record| {
+        record.roles_pinged.iter().for_each(|role_id| {
+            let (pings, joins) = joins_by_role.entry(*role_id).or_default();
+            *pings += 1;
+            *joins += record.role_joins.get(role_id).copied().unwrap_or_default();
+        });
+    });
+    let mut joins_by_role: Vec<(RoleId, (usize, usize))> = joins_by_role.into_iter().collect();
+    joins_by_role.sort_by(|(_, (a_pings, a_joins)), (_, (b_pings, b_joins))| {
+        (b_joins * a_pings).cmp(&(a_joins * b_pings))
+    });
+    message.push_line("### Role Responses");
+    joins_by_role.iter().for_each(|(role_id, (pings, joins))| {
+        message.mention(role_id).push_line(format!(
+            " - pinged {} times, {:.1} joins per ping",
+            pings,
+            *joins as f64 / *pings as f64
+        ));
+    });
+    message.build()
+}
+
+/// The `limit` keys with the highest counts, highest first.
+fn top_counts<K: Ord + Copy>(counts: HashMap<K, usize>, limit: usize) -> Vec<(K, usize)> {
+    let mut counts: Vec<(K, usize)> = counts.into_iter().collect();
+    counts
+        .sort_by(|(a_key, a_count), (b_key, b_count)| b_count.cmp(a_count).then(a_key.cmp(b_key)));
+    counts.truncate(limit);
+    counts
+}
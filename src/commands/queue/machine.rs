@@ -0,0 +1,568 @@
+use std::time::Duration;
+
+use serenity::model::prelude::*;
+
+use super::state::{QueuePhase, QueueState};
+use crate::clock_time::now_secs;
+
+/// Where the queue gets the time from, so tests can move it along by hand.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        now_secs()
+    }
+}
+
+/// Something that happened to a running queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueEvent {
+    /// The queue's timer went off.
+    Timer,
+    /// A user pressed the join button.
+    Toggle(UserId),
+    /// Everyone currently interested in the queue's Scheduled Event.
+    EventInterested(Vec<UserId>),
+    /// The owner asked for the next game to start, from the phase the queue
+    /// was in when they pressed the button.
+    StartNow(QueuePhase),
+    /// A ready check finished, dropping everyone who wasn't ready.
+    ReadyCheckOver {
+        dropped: Vec<UserId>,
+    },
+    Extend,
+    Cancel,
+}
+
+/// Something the queue needs done in Discord after an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueEffect {
+    /// The queue message is out of date.
+    Redraw,
+    /// The user joined, and should be added to the queue thread.
+    Joined(UserId),
+    /// A game started with this party.
+    GameStarted {
+        party: Vec<UserId>,
+        first_game: bool,
+    },
+    /// The next game's party filled up while a game is running.
+    PartyFull(Vec<UserId>),
+    /// The last game's match window is over, and its result can be reported.
+    MatchOver(Vec<UserId>),
+    /// The countdown moved, and the queue's event should move with it.
+    Rescheduled,
+    /// The queue is over, with whoever was still waiting for a game.
+    Closed {
+        cancelled: bool,
+        waiting: Vec<UserId>,
+    },
+}
+
+/// The queue lifecycle, counting down to the first game, open for the next
+/// one, and closed, without anything Discord in the way.
+pub struct QueueMachine<C> {
+    clock: C,
+    match_length: Duration,
+    extend_by: Duration,
+}
+
+impl<C: Clock> QueueMachine<C> {
+    pub fn new(clock: C, match_length: Duration, extend_by: Duration) -> Self {
+        QueueMachine {
+            clock,
+            match_length,
+            extend_by,
+        }
+    }
+
+    /// Applies the event to the queue, returning what needs doing about it.
+    /// Nothing should be fed to a queue once it has closed.
+    pub fn handle(&self, queue: &mut QueueState, event: QueueEvent) -> Vec<QueueEffect> {
+        match event {
+            QueueEvent::Timer => {
+                // The queue may have been pushed back or started from
+                // elsewhere while the timer was running.
+                if self.clock.now() < queue.wake_at() {
+                    return vec![];
+                }
+                match queue.phase {
+                    QueuePhase::Countdown => self.start_game(queue),
+                    QueuePhase::Open => {
+                        let mut effects = match_over(queue);
+                        effects.push(QueueEffect::Closed {
+                            cancelled: false,
+                            waiting: queue.users_waiting.clone(),
+                        });
+                        effects
+                    }
+                }
+            }
+            QueueEvent::Toggle(user_id) => {
+                let filled = queue.toggle_user(user_id);
+                let mut effects = vec![];
+                if queue.has_joined(user_id) {
+                    effects.push(QueueEffect::Joined(user_id));
+                }
+                match (filled, queue.phase) {
+                    (true, QueuePhase::Countdown) => effects.extend(self.start_game(queue)),
+                    (true, QueuePhase::Open) => {
+                        effects.push(QueueEffect::PartyFull(queue.users_waiting.clone()));
+                        effects.push(QueueEffect::Redraw);
+                    }
+                    (false, _) => effects.push(QueueEffect::Redraw),
+                }
+                effects
+            }
+            QueueEvent::EventInterested(interested_users) => {
+                if queue.phase != QueuePhase::Countdown {
+                    return vec![];
+                }
+                let mut effects = vec![];
+                let mut filled = false;
+                for user_id in interested_users {
+                    // Users are only pulled in once, so leaving the queue
+                    // isn't undone by the next sync.
+                    if queue.event_users.contains(&user_id) {
+                        continue;
+                    }
+                    queue.event_users.push(user_id);
+                    if !queue.has_joined(user_id) {
+                        filled |= queue.toggle_user(user_id);
+                        effects.push(QueueEffect::Joined(user_id));
+                    }
+                }
+                if filled {
+                    effects.extend(self.start_game(queue));
+                } else if !effects.is_empty() {
+                    effects.push(QueueEffect::Redraw);
+                }
+                effects
+            }
+            QueueEvent::StartNow(pressed_in) => {
+                // A press from before the first game started shouldn't start
+                // the second one too.
+                if pressed_in != queue.phase {
+                    return vec![QueueEffect::Redraw];
+                }
+                self.start_game(queue)
+            }
+            QueueEvent::ReadyCheckOver { dropped } => {
+                queue.ready_check_running = false;
+                for user_id in dropped {
+                    // Only the next game's spots are given up, and leaving
+                    // promotes the waitlist into them.
+                    if queue.users_waiting.contains(&user_id) {
+                        queue.toggle_user(user_id);
+                    }
+                }
+                vec![QueueEffect::Redraw]
+            }
+            QueueEvent::Extend => match queue.phase {
+                QueuePhase::Countdown => {
+                    queue.queue_at += self.extend_by.as_secs();
+                    vec![QueueEffect::Rescheduled, QueueEffect::Redraw]
+                }
+                QueuePhase::Open => {
+                    queue.match_at += self.extend_by.as_secs();
+                    vec![QueueEffect::Redraw]
+                }
+            },
+            QueueEvent::Cancel => vec![QueueEffect::Closed {
+                cancelled: true,
+                waiting: vec![],
+            }],
+        }
+    }
+
+    fn start_game(&self, queue: &mut QueueState) -> Vec<QueueEffect> {
+        let first_game = queue.phase == QueuePhase::Countdown;
+        let mut effects = if first_game {
+            vec![]
+        } else {
+            match_over(queue)
+        };
+        let party = queue.start_next_game(self.clock.now(), self.match_length.as_secs());
+        effects.push(QueueEffect::GameStarted { party, first_game });
+        effects.push(QueueEffect::Redraw);
+        effects
+    }
+}
+
+/// Lets the last game's party report how it went, if anyone played.
+fn match_over(queue: &QueueState) -> Vec<QueueEffect> {
+    if queue.last_party.is_empty() {
+        vec![]
+    } else {
+        vec![QueueEffect::MatchOver(queue.last_party.clone())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    const MATCH_LENGTH_SECS: u64 = 40 * 60;
+    const EXTEND_SECS: u64 = 5 * 60;
+
+    #[derive(Clone, Default)]
+    struct FakeClock(Rc<Cell<u64>>);
+
+    impl FakeClock {
+        fn set(&self, now: u64) {
+            self.0.set(now);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    /// Stands in for Discord, handing out button presses at set times and
+    /// firing the queue's timer whenever the clock passes it on the way.
+    struct FakeInteractions {
+        presses: Vec<(u64, QueueEvent)>,
+    }
+
+    impl FakeInteractions {
+        fn new(presses: Vec<(u64, QueueEvent)>) -> Self {
+            FakeInteractions { presses }
+        }
+
+        /// Plays every press, then lets the clock run until the queue closes
+        /// or `until` comes around. Returns everything the queue asked for.
+        fn play(
+            self,
+            machine: &QueueMachine<FakeClock>,
+            queue: &mut QueueState,
+            until: u64,
+        ) -> Vec<QueueEffect> {
+            let mut effects = vec![];
+            let presses = self
+                .presses
+                .into_iter()
+                .map(|(at, event)| (at, Some(event)));
+            for (at, event) in presses.chain([(until, None)]) {
+                while queue.wake_at() <= at {
+                    machine.clock.set(queue.wake_at());
+                    if record(&mut effects, machine.handle(queue, QueueEvent::Timer)) {
+                        return effects;
+                    }
+                }
+                machine.clock.set(at);
+                if let Some(event) = event {
+                    if record(&mut effects, machine.handle(queue, event)) {
+                        return effects;
+                    }
+                }
+            }
+            effects
+        }
+    }
+
+    /// Keeps the new effects, returning whether they closed the queue.
+    fn record(effects: &mut Vec<QueueEffect>, new_effects: Vec<QueueEffect>) -> bool {
+        let closed = new_effects
+            .iter()
+            .any(|effect| matches!(effect, QueueEffect::Closed { .. }));
+        effects.extend(new_effects);
+        closed
+    }
+
+    fn machine() -> (FakeClock, QueueMachine<FakeClock>) {
+        let clock = FakeClock::default();
+        let machine = QueueMachine::new(
+            clock.clone(),
+            Duration::from_secs(MATCH_LENGTH_SECS),
+            Duration::from_secs(EXTEND_SECS),
+        );
+        (clock, machine)
+    }
+
+    fn queue(queue_at: u64, party_size: Option<usize>) -> QueueState {
+        QueueState {
+            queue_at,
+            party_size,
+            ..Default::default()
+        }
+    }
+
+    fn user(id: u64) -> UserId {
+        UserId::new(id)
+    }
+
+    #[test]
+    fn toggling_joins_and_leaves() {
+        let (_, machine) = machine();
+        let mut queue = queue(100, None);
+
+        let effects = machine.handle(&mut queue, QueueEvent::Toggle(user(1)));
+        assert_eq!(
+            effects,
+            vec![QueueEffect::Joined(user(1)), QueueEffect::Redraw]
+        );
+        assert_eq!(queue.users_waiting, vec![user(1)]);
+
+        let effects = machine.handle(&mut queue, QueueEvent::Toggle(user(1)));
+        assert_eq!(effects, vec![QueueEffect::Redraw]);
+        assert!(queue.users_waiting.is_empty());
+        assert_eq!(queue.joined_users, vec![user(1)]);
+    }
+
+    #[test]
+    fn leaving_a_full_party_promotes_the_waitlist() {
+        let (_, machine) = machine();
+        let mut queue = queue(100, Some(2));
+        queue.phase = QueuePhase::Open;
+        queue.match_at = 1_000;
+
+        for id in 1..=3 {
+            machine.handle(&mut queue, QueueEvent::Toggle(user(id)));
+        }
+        assert_eq!(queue.users_waiting, vec![user(1), user(2)]);
+        assert_eq!(queue.waitlist, vec![user(3)]);
+
+        machine.handle(&mut queue, QueueEvent::Toggle(user(1)));
+        assert_eq!(queue.users_waiting, vec![user(2), user(3)]);
+        assert!(queue.waitlist.is_empty());
+    }
+
+    #[test]
+    fn ready_check_drops_give_spots_to_the_waitlist() {
+        let (_, machine) = machine();
+        let mut queue = queue(0, Some(2));
+        queue.phase = QueuePhase::Open;
+        queue.match_at = 1_000;
+        queue.ready_check_running = true;
+
+        for id in 1..=4 {
+            machine.handle(&mut queue, QueueEvent::Toggle(user(id)));
+        }
+        let effects = machine.handle(
+            &mut queue,
+            QueueEvent::ReadyCheckOver {
+                dropped: vec![user(1), user(4)],
+            },
+        );
+        assert_eq!(effects, vec![QueueEffect::Redraw]);
+        assert_eq!(queue.users_waiting, vec![user(2), user(3)]);
+        assert_eq!(queue.waitlist, vec![user(4)]);
+        assert!(!queue.ready_check_running);
+    }
+
+    #[test]
+    fn countdown_runs_out_into_the_first_game() {
+        let (clock, machine) = machine();
+        let mut queue = queue(600, None);
+
+        let effects = FakeInteractions::new(vec![
+            (10, QueueEvent::Toggle(user(1))),
+            (20, QueueEvent::Toggle(user(2))),
+        ])
+        .play(&machine, &mut queue, 601);
+
+        assert!(effects.contains(&QueueEffect::GameStarted {
+            party: vec![user(1), user(2)],
+            first_game: true,
+        }));
+        assert_eq!(clock.now(), 601);
+        assert_eq!(queue.phase, QueuePhase::Open);
+        assert_eq!(queue.first_game_at, Some(600));
+        assert_eq!(queue.match_at, 600 + MATCH_LENGTH_SECS);
+        assert!(queue.users_waiting.is_empty());
+    }
+
+    #[test]
+    fn early_timer_does_nothing() {
+        let (clock, machine) = machine();
+        let mut queue = queue(600, None);
+        clock.set(599);
+
+        assert!(machine.handle(&mut queue, QueueEvent::Timer).is_empty());
+        assert_eq!(queue.phase, QueuePhase::Countdown);
+    }
+
+    #[test]
+    fn filling_the_party_starts_the_game_early() {
+        let (_, machine) = machine();
+        let mut queue = queue(600, Some(2));
+
+        let effects = FakeInteractions::new(vec![
+            (10, QueueEvent::Toggle(user(1))),
+            (20, QueueEvent::Toggle(user(2))),
+        ])
+        .play(&machine, &mut queue, 30);
+
+        assert!(effects.contains(&QueueEffect::GameStarted {
+            party: vec![user(1), user(2)],
+            first_game: true,
+        }));
+        assert_eq!(queue.queue_at, 20);
+        assert_eq!(queue.match_at, 20 + MATCH_LENGTH_SECS);
+    }
+
+    #[test]
+    fn filling_the_next_game_pings_instead_of_starting() {
+        let (_, machine) = machine();
+        let mut queue = queue(0, Some(2));
+        queue.phase = QueuePhase::Open;
+        queue.match_at = 1_000;
+
+        let effects = FakeInteractions::new(vec![
+            (10, QueueEvent::Toggle(user(1))),
+            (20, QueueEvent::Toggle(user(2))),
+        ])
+        .play(&machine, &mut queue, 30);
+
+        assert!(effects.contains(&QueueEffect::PartyFull(vec![user(1), user(2)])));
+        assert!(!effects
+            .iter()
+            .any(|effect| matches!(effect, QueueEffect::GameStarted { .. })));
+    }
+
+    #[test]
+    fn match_window_closes_the_queue() {
+        let (_, machine) = machine();
+        let mut queue = queue(100, None);
+
+        let effects = FakeInteractions::new(vec![
+            (10, QueueEvent::Toggle(user(1))),
+            (200, QueueEvent::Toggle(user(2))),
+        ])
+        .play(&machine, &mut queue, 10_000);
+
+        assert_eq!(
+            effects.last(),
+            Some(&QueueEffect::Closed {
+                cancelled: false,
+                waiting: vec![user(2)],
+            })
+        );
+    }
+
+    #[test]
+    fn every_played_match_gets_reported() {
+        let (_, machine) = machine();
+        let mut queue = queue(100, None);
+
+        let effects = FakeInteractions::new(vec![
+            (10, QueueEvent::Toggle(user(1))),
+            (200, QueueEvent::Toggle(user(2))),
+            (300, QueueEvent::StartNow(QueuePhase::Open)),
+        ])
+        .play(&machine, &mut queue, 10_000);
+
+        let reported: Vec<&QueueEffect> = effects
+            .iter()
+            .filter(|effect| matches!(effect, QueueEffect::MatchOver(_)))
+            .collect();
+        assert_eq!(
+            reported,
+            vec![
+                &QueueEffect::MatchOver(vec![user(1)]),
+                &QueueEffect::MatchOver(vec![user(2)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn extending_pushes_back_the_current_phase() {
+        let (_, machine) = machine();
+        let mut queue = queue(600, None);
+
+        let effects = machine.handle(&mut queue, QueueEvent::Extend);
+        assert_eq!(effects, vec![QueueEffect::Rescheduled, QueueEffect::Redraw]);
+        assert_eq!(queue.queue_at, 600 + EXTEND_SECS);
+
+        let effects = FakeInteractions::new(vec![]).play(&machine, &mut queue, 600 + EXTEND_SECS);
+        assert!(effects.contains(&QueueEffect::GameStarted {
+            party: vec![],
+            first_game: true,
+        }));
+
+        let match_at = queue.match_at;
+        assert_eq!(
+            machine.handle(&mut queue, QueueEvent::Extend),
+            vec![QueueEffect::Redraw]
+        );
+        assert_eq!(queue.match_at, match_at + EXTEND_SECS);
+    }
+
+    #[test]
+    fn start_now_only_counts_for_the_phase_it_was_pressed_in() {
+        let (clock, machine) = machine();
+        let mut queue = queue(600, None);
+        clock.set(50);
+
+        let effects = machine.handle(&mut queue, QueueEvent::StartNow(QueuePhase::Countdown));
+        assert!(effects.contains(&QueueEffect::GameStarted {
+            party: vec![],
+            first_game: true,
+        }));
+        assert_eq!(queue.queue_at, 50);
+
+        let effects = machine.handle(&mut queue, QueueEvent::StartNow(QueuePhase::Countdown));
+        assert_eq!(effects, vec![QueueEffect::Redraw]);
+
+        clock.set(70);
+        let effects = machine.handle(&mut queue, QueueEvent::StartNow(QueuePhase::Open));
+        assert!(effects.contains(&QueueEffect::GameStarted {
+            party: vec![],
+            first_game: false,
+        }));
+        assert_eq!(queue.queue_at, 70);
+    }
+
+    #[test]
+    fn event_users_are_only_pulled_in_once() {
+        let (_, machine) = machine();
+        let mut queue = queue(600, None);
+
+        let effects = machine.handle(
+            &mut queue,
+            QueueEvent::EventInterested(vec![user(1), user(2)]),
+        );
+        assert_eq!(
+            effects,
+            vec![
+                QueueEffect::Joined(user(1)),
+                QueueEffect::Joined(user(2)),
+                QueueEffect::Redraw,
+            ]
+        );
+
+        machine.handle(&mut queue, QueueEvent::Toggle(user(1)));
+        let effects = machine.handle(
+            &mut queue,
+            QueueEvent::EventInterested(vec![user(1), user(2)]),
+        );
+        assert!(effects.is_empty());
+        assert_eq!(queue.users_waiting, vec![user(2)]);
+    }
+
+    #[test]
+    fn cancelling_closes_without_anyone_waiting() {
+        let (_, machine) = machine();
+        let mut queue = queue(600, None);
+        machine.handle(&mut queue, QueueEvent::Toggle(user(1)));
+
+        let effects =
+            FakeInteractions::new(vec![(10, QueueEvent::Cancel)]).play(&machine, &mut queue, 20);
+        assert_eq!(
+            effects,
+            vec![QueueEffect::Closed {
+                cancelled: true,
+                waiting: vec![],
+            }]
+        );
+    }
+}
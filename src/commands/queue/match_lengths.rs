@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use super::state::QueuePhase;
+use super::QueueCommand;
+use crate::clock_time::now_secs;
+use crate::settings::GameQueues;
+
+/// How many of the latest sessions each game's estimate is taken from.
+const MAX_SAMPLES: usize = 20;
+/// Sessions needed before the estimate takes over from `match_length`.
+const MIN_SAMPLES: usize = 5;
+/// Sessions shorter or longer than these are someone popping in, or a
+/// channel left open all night, rather than a game.
+const MIN_SAMPLE_SECS: u64 = 5 * 60;
+const MAX_SAMPLE_SECS: u64 = 4 * 60 * 60;
+/// How many people need to be in a queue's voice channel for it to count
+/// as the group playing.
+const MIN_VOICE_PARTY: usize = 2;
+
+/// How long recent groups of each game stayed together in voice.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MatchLengths {
+    samples: HashMap<String, Vec<u64>>,
+}
+
+impl MatchLengths {
+    /// Records a voice session, keeping only the latest few per game.
+    /// Returns false if the session doesn't look like a game.
+    pub fn add_sample(&mut self, game_name: &str, session_secs: u64) -> bool {
+        if !(MIN_SAMPLE_SECS..=MAX_SAMPLE_SECS).contains(&session_secs) {
+            return false;
+        }
+        let samples = self.samples.entry(game_name.to_string()).or_default();
+        samples.push(session_secs);
+        if samples.len() > MAX_SAMPLES {
+            samples.remove(0);
+        }
+        true
+    }
+
+    /// The median session for the game, once there are enough of them.
+    pub fn estimate(&self, game_name: &str) -> Option<Duration> {
+        let mut samples = self.samples.get(game_name)?.clone();
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        samples.sort_unstable();
+        Some(Duration::from_secs(samples[samples.len() / 2]))
+    }
+}
+
+/// A group playing together in a queue's voice channel.
+pub struct VoiceSession {
+    game_name: String,
+    started_at: u64,
+}
+
+impl QueueCommand {
+    /// Roughly how long a match of the game takes, learned from voice once
+    /// there's enough to go on and the configured `match_length` until then.
+    pub(super) fn match_length(&self, game_queue: &GameQueues) -> Duration {
+        self.match_lengths
+            .read(|match_lengths| match_lengths.estimate(&game_queue.game_name))
+            .unwrap_or(game_queue.match_length)
+    }
+
+    /// Times how long groups stay together in the voice channels of running
+    /// queues, starting once the group is in and ending once it breaks up.
+    pub async fn voice_state_update(
+        &self,
+        ctx: &Context,
+        old: Option<&VoiceState>,
+        new: &VoiceState,
+    ) {
+        let Some(guild_id) = new.guild_id else {
+            return;
+        };
+        let channel_ids = [old.and_then(|old| old.channel_id), new.channel_id];
+        for channel_id in channel_ids.into_iter().flatten() {
+            let in_voice = ctx
+                .cache
+                .guild(guild_id)
+                .map(|guild| {
+                    guild
+                        .voice_states
+                        .values()
+                        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+                        .count()
+                })
+                .unwrap_or_default();
+            if in_voice >= MIN_VOICE_PARTY {
+                self.start_voice_session(channel_id);
+            } else {
+                self.end_voice_session(channel_id);
+            }
+        }
+    }
+
+    fn start_voice_session(&self, channel_id: ChannelId) {
+        // Only groups whose game has started count, not people hanging
+        // around in a channel reserved for a queue that's still counting down.
+        let Some(game_name) = self.queues.read(|queues| {
+            queues
+                .values()
+                .find(|queue| {
+                    queue.voice_channel_id == Some(channel_id) && queue.phase == QueuePhase::Open
+                })
+                .map(|queue| queue.game_name.clone())
+        }) else {
+            return;
+        };
+        self.voice_sessions
+            .lock()
+            .unwrap()
+            .entry(channel_id)
+            .or_insert(VoiceSession {
+                game_name,
+                started_at: now_secs(),
+            });
+    }
+
+    /// Ends the channel's session, even if its queue has closed since, and
+    /// learns from how long it ran.
+    fn end_voice_session(&self, channel_id: ChannelId) {
+        let Some(voice_session) = self.voice_sessions.lock().unwrap().remove(&channel_id) else {
+            return;
+        };
+        let session_secs = now_secs().saturating_sub(voice_session.started_at);
+        self.match_lengths.update(|match_lengths| {
+            match_lengths.add_sample(&voice_session.game_name, session_secs)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_estimate_until_enough_samples() {
+        let mut match_lengths = MatchLengths::default();
+        for _ in 1..MIN_SAMPLES {
+            assert!(match_lengths.add_sample("Deadlock", 35 * 60));
+        }
+        assert_eq!(match_lengths.estimate("Deadlock"), None);
+
+        match_lengths.add_sample("Deadlock", 35 * 60);
+        assert_eq!(
+            match_lengths.estimate("Deadlock"),
+            Some(Duration::from_secs(35 * 60))
+        );
+        assert_eq!(match_lengths.estimate("Other Game"), None);
+    }
+
+    #[test]
+    fn estimate_follows_recent_sessions() {
+        let mut match_lengths = MatchLengths::default();
+        assert!(!match_lengths.add_sample("Deadlock", 60));
+        assert!(!match_lengths.add_sample("Deadlock", 10 * 60 * 60));
+
+        for _ in 0..MAX_SAMPLES {
+            match_lengths.add_sample("Deadlock", 30 * 60);
+        }
+        for _ in 0..MAX_SAMPLES / 2 + 1 {
+            match_lengths.add_sample("Deadlock", 50 * 60);
+        }
+        assert_eq!(
+            match_lengths.estimate("Deadlock"),
+            Some(Duration::from_secs(50 * 60))
+        );
+    }
+}
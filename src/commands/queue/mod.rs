@@ -0,0 +1,1685 @@
+mod cooldowns;
+mod embed;
+mod error;
+mod events;
+mod history;
+mod machine;
+mod match_lengths;
+mod ready_check;
+mod results;
+mod scheduled;
+mod selection;
+mod state;
+mod suggestions;
+mod teams;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serenity::builder::*;
+use serenity::collector::ComponentInteractionCollector;
+use serenity::futures::future::join_all;
+use serenity::futures::StreamExt;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::clock_time::{now_secs, TimeZone};
+use crate::duration::format_duration;
+use crate::match_results::MatchResultsStore;
+use crate::settings::{GameQueues, QueueVoiceChannelName, ReactionRole, Settings};
+use crate::store::Store;
+use crate::user_settings::{QueueChoices, UserSettingsStore};
+use crate::voice_expander;
+use cooldowns::{PingCooldowns, RoleReadyAt};
+use embed::{build_closed_queue_embed, build_queue_embed, build_queue_pings};
+use error::{send_with_retries, with_retries, QueueError};
+use history::{build_stats_message, QueueRecord};
+use machine::{QueueEffect, QueueEvent, QueueMachine, SystemClock};
+use match_lengths::{MatchLengths, VoiceSession};
+use selection::{
+    check_queue_allowed, resolve_at_time, QueueRequest, QueueSelection, SelectionMenu,
+    SelectionStep,
+};
+use state::{QueuePhase, QueueState};
+use suggestions::GameSightings;
+use teams::{balanced_teams, build_teams_embed, random_teams, Teams};
+
+use tokio::time::sleep;
+
+const QUEUE_EXTEND_SECS: u64 = 5 * 60;
+/// Buttons only the person who started the queue may press.
+const OWNER_BUTTONS: [&str; 5] = [
+    "queue_start_now",
+    "queue_extend",
+    "queue_cancel",
+    "queue_make_teams",
+    "queue_ready_check",
+];
+/// How long the buttons under a team split keep working.
+const TEAMS_BUTTON_TIMEOUT_SECS: u64 = 15 * 60;
+/// How often a queue with a Scheduled Event checks for newly interested users.
+const EVENT_SYNC_SECS: u64 = 60;
+
+#[derive(Clone)]
+pub struct QueueCommand {
+    settings: Settings,
+    queues: Store<HashMap<MessageId, QueueState>>,
+    resumed: Arc<AtomicBool>,
+    scheduler_started: Arc<AtomicBool>,
+    ping_cooldowns: Arc<Mutex<PingCooldowns>>,
+    game_sightings: Arc<Mutex<GameSightings>>,
+    user_settings: UserSettingsStore,
+    history: Store<Vec<QueueRecord>>,
+    match_results: MatchResultsStore,
+    match_lengths: Store<MatchLengths>,
+    voice_sessions: Arc<Mutex<HashMap<ChannelId, VoiceSession>>>,
+    /// Held while a queue claims a voice channel.
+    voice_claims: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl QueueCommand {
+    pub async fn run(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        let result = match queue_command.data.options.first() {
+            Some(subcommand) if subcommand.name == "stats" => self.stats(ctx, queue_command).await,
+            Some(subcommand) if subcommand.name == "skill" => self.skill(ctx, queue_command).await,
+            Some(subcommand) if subcommand.name == "list" => self.list(ctx, queue_command).await,
+            Some(subcommand) if subcommand.name == "again" => self.again(ctx, queue_command).await,
+            _ => self.start(ctx, queue_command).await,
+        };
+        match result {
+            Err(QueueError::User(why)) => {
+                QueueCommand::reply_ephemeral(ctx, queue_command, why).await
+            }
+            result => result,
+        }
+    }
+
+    /// Tells the user why their command didn't work, whether or not it has
+    /// been responded to yet.
+    async fn reply_ephemeral(
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+        content: String,
+    ) -> Result<(), QueueError> {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(content.clone()),
+        );
+        if queue_command.create_response(ctx, response).await.is_ok() {
+            return Ok(());
+        }
+        with_retries(|| {
+            queue_command.edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content(content.clone())
+                    .components(vec![]),
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Shows every running queue in the server, with a menu to join or leave
+    /// any of them without scrolling back to the queue message.
+    async fn list(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        let (content, components) =
+            self.build_queue_list(queue_command.guild_id, queue_command.user.id);
+        queue_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(content)
+                        .components(components),
+                ),
+            )
+            .await?;
+
+        let mut list_select_stream = queue_command
+            .get_response(&ctx)
+            .await?
+            .await_component_interactions(ctx)
+            .timeout(Duration::from_secs(60 * 2))
+            .stream();
+        while let Some(list_interaction) = list_select_stream.next().await {
+            // Joining can start a game, which takes longer than Discord waits
+            // for an answer.
+            list_interaction
+                .create_response(&ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            if let ComponentInteractionDataKind::StringSelect { values } =
+                &list_interaction.data.kind
+            {
+                if let Ok(message_id) = values[0].parse::<u64>() {
+                    let message_id = MessageId::new(message_id);
+                    if let Err(why) = self
+                        .apply(
+                            ctx,
+                            message_id,
+                            QueueEvent::Toggle(list_interaction.user.id),
+                        )
+                        .await
+                    {
+                        self.handle_queue_error(ctx, message_id, why).await;
+                    }
+                }
+            }
+            let (content, components) =
+                self.build_queue_list(queue_command.guild_id, queue_command.user.id);
+            queue_command
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new()
+                        .content(content)
+                        .components(components),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn build_queue_list(
+        &self,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> (String, Vec<CreateActionRow>) {
+        let mut queues: Vec<QueueState> = self.queues.read(|queues| {
+            queues
+                .values()
+                .filter(|queue| queue.guild_id == guild_id)
+                .cloned()
+                .collect()
+        });
+        queues.sort_by_key(|queue| queue.queue_at);
+
+        if queues.is_empty() {
+            return ("No queues are running right now".to_string(), vec![]);
+        }
+
+        let mut queue_list = MessageBuilder::new();
+        queues.iter().for_each(|queue| {
+            let joined = match queue.party_size {
+                Some(party_size) => format!("{}/{}", queue.users_waiting.len(), party_size),
+                None => queue.users_waiting.len().to_string(),
+            };
+            let starts = match queue.phase {
+                QueuePhase::Countdown => "Queueing",
+                QueuePhase::Open => "Started",
+            };
+            queue_list
+                .push_line(format!("### {} - {}", queue.game_name, queue.owner_name))
+                .push_line(format!(
+                    "{} <t:{}:R> (<t:{}:F>) · {} joined · {}",
+                    starts,
+                    queue.queue_at,
+                    queue.queue_at,
+                    joined,
+                    queue.message_id.link(queue.channel_id, queue.guild_id)
+                ));
+        });
+
+        let list_menu = CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                "queue_list_toggle",
+                CreateSelectMenuKind::String {
+                    options: queues
+                        .iter()
+                        .take(25)
+                        .map(|queue| {
+                            let joined = queue.users_waiting.contains(&user_id)
+                                || queue.waitlist.contains(&user_id);
+                            CreateSelectMenuOption::new(
+                                format!(
+                                    "{} {} - {}",
+                                    if joined { "Leave" } else { "Join" },
+                                    queue.game_name,
+                                    queue.owner_name
+                                ),
+                                queue.message_id.to_string(),
+                            )
+                        })
+                        .collect(),
+                },
+            )
+            .placeholder("Join or Leave a Queue"),
+        );
+        (queue_list.build(), vec![list_menu])
+    }
+
+    async fn skill(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        let skill_rating = QueueCommand::command_option(queue_command, "rating")
+            .and_then(|rating| rating.as_i64())
+            .map(|rating| rating as u32);
+        self.user_settings.update(|user_settings| {
+            user_settings
+                .entry(queue_command.user.id)
+                .or_default()
+                .skill_rating = skill_rating;
+        });
+
+        queue_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(format!(
+                            "Your skill rating is now {}",
+                            skill_rating.unwrap_or_default()
+                        )),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn stats(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        let game_name = QueueCommand::string_option(queue_command, "game");
+        let stats_message = self.history.read(|history| {
+            let records: Vec<&QueueRecord> = history
+                .iter()
+                .filter(|record| !record.cancelled)
+                .filter(|record| {
+                    game_name.is_none() || game_name == Some(record.game_name.as_str())
+                })
+                .collect();
+            build_stats_message(&records, now_secs())
+        });
+
+        queue_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(stats_message)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn start(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        queue_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                ),
+            )
+            .await?;
+        let game_roles: HashMap<String, Vec<ReactionRole>> = self
+            .settings
+            .game_queue
+            .iter()
+            .map(|game_queue| {
+                (
+                    game_queue.game_name.clone(),
+                    self.game_queue_roles(game_queue),
+                )
+            })
+            .collect();
+        let mut selection = QueueSelection::new(
+            SystemClock,
+            &self.settings.game_queue,
+            &game_roles,
+            QueueRequest {
+                game: QueueCommand::string_option(queue_command, "game").map(str::to_string),
+                wait: QueueCommand::string_option(queue_command, "in").map(str::to_string),
+                at_time: QueueCommand::string_option(queue_command, "at").map(str::to_string),
+                roles: QueueCommand::string_option(queue_command, "roles").map(str::to_string),
+                party_size: QueueCommand::command_option(queue_command, "party_size")
+                    .and_then(|party_size| party_size.as_i64())
+                    .map(|party_size| party_size as usize),
+                channel_id: queue_command.channel_id,
+                member_roles: queue_command
+                    .member
+                    .as_ref()
+                    .map(|member| member.roles.clone()),
+                time_zone: self.time_zone(queue_command.user.id),
+                last_queue: self.last_queue(queue_command.user.id),
+            },
+        );
+        let choices = loop {
+            let menu = match selection.next().map_err(QueueError::User)? {
+                SelectionStep::Ask(menu) => menu,
+                SelectionStep::Done(choices) => break choices,
+            };
+            let Some(values) = QueueCommand::ask(
+                ctx,
+                queue_command,
+                self.build_selection_menu(&menu, &game_roles),
+            )
+            .await?
+            else {
+                return Ok(());
+            };
+            selection.pick(&values).map_err(QueueError::User)?;
+        };
+        self.open_queue(ctx, queue_command, choices).await
+    }
+
+    /// Starts a queue with the same choices as the user's last one, skipping
+    /// all the menus.
+    async fn again(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+    ) -> Result<(), QueueError> {
+        queue_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                ),
+            )
+            .await?;
+
+        let Some(mut choices) = self
+            .last_queue(queue_command.user.id)
+            .filter(|last_queue| self.game_queue(&last_queue.game_name).is_some())
+        else {
+            return Err(QueueError::User(
+                "There's no queue of yours to repeat yet, use /queue start".to_string(),
+            ));
+        };
+        let game_queue = self
+            .game_queue(&choices.game_name)
+            .expect("Checked the game is still configured");
+        check_queue_allowed(
+            game_queue,
+            queue_command
+                .member
+                .as_ref()
+                .map(|member| member.roles.as_slice()),
+            queue_command.channel_id,
+        )
+        .map_err(QueueError::User)?;
+        if let Some(at_time) = &choices.at_time {
+            let now = now_secs();
+            choices.wait_secs =
+                resolve_at_time(at_time, self.time_zone(queue_command.user.id), now)
+                    .map_err(QueueError::User)?
+                    .saturating_sub(now);
+        }
+        self.open_queue(ctx, queue_command, choices).await
+    }
+
+    fn last_queue(&self, user_id: UserId) -> Option<QueueChoices> {
+        self.user_settings.read(|user_settings| {
+            user_settings
+                .get(&user_id)
+                .and_then(|user_settings| user_settings.last_queue.clone())
+        })
+    }
+
+    /// Posts a queue from the user's choices, remembering them for next time,
+    /// and runs it until it's over.
+    async fn open_queue(
+        &self,
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+        choices: QueueChoices,
+    ) -> Result<(), QueueError> {
+        let game_queue = self
+            .game_queue(&choices.game_name)
+            .expect("Queue choices are for a configured game");
+        self.user_settings.update(|user_settings| {
+            user_settings
+                .entry(queue_command.user.id)
+                .or_default()
+                .last_queue = Some(choices.clone());
+        });
+        let seconds_to_wait_value = choices.wait_secs;
+        let started_at = now_secs();
+        let (roles, muted_roles) = self.take_role_pings(queue_command, &choices.roles, started_at);
+
+        let owner = &queue_command.user;
+        let mut queue = QueueState {
+            game_name: game_queue.game_name.clone(),
+            owner_id: owner.id,
+            owner_name: owner.global_name.clone().unwrap_or(owner.name.clone()),
+            owner_avatar_url: Some(owner.face()),
+            guild_id: queue_command.guild_id,
+            channel_id: queue_command.channel_id,
+            roles,
+            muted_roles: muted_roles.iter().map(|(role_id, _)| *role_id).collect(),
+            started_at,
+            queue_at: started_at + seconds_to_wait_value,
+            match_at: started_at + seconds_to_wait_value + self.match_length(game_queue).as_secs(),
+            party_size: choices.party_size.or(game_queue.party_size),
+            ..Default::default()
+        };
+        let with_event = game_queue.scheduled_event_after.is_some_and(|event_after| {
+            choices.at_time.is_some() || seconds_to_wait_value >= event_after.as_secs()
+        });
+        self.post_queue(ctx, &mut queue, game_queue, with_event)
+            .await?;
+
+        // The queue is up, so it runs even if tidying up the reply fails.
+        let reply_result = if muted_roles.is_empty() {
+            queue_command.delete_response(&ctx).await
+        } else {
+            let mut cooldown_message = MessageBuilder::new();
+            cooldown_message.push_line("Queue posted, but some roles were pinged too recently:");
+            muted_roles.iter().for_each(|(role_id, ready_at)| {
+                cooldown_message
+                    .push("You can ping ")
+                    .mention(role_id)
+                    .push_line(format!(" again <t:{}:R>", ready_at));
+            });
+            queue_command
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new()
+                        .content(cooldown_message.build())
+                        .components(vec![])
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await
+                .map(|_| ())
+        };
+        if let Err(why) = reply_result {
+            println!("Cannot update the queue reply: {why}");
+        }
+        self.drive(ctx, queue.message_id).await;
+        Ok(())
+    }
+
+    /// Posts the queue message, with its thread and event if it gets them,
+    /// and starts tracking it. The caller still has to drive it.
+    async fn post_queue(
+        &self,
+        ctx: &Context,
+        queue: &mut QueueState,
+        game_queue: &GameQueues,
+        with_event: bool,
+    ) -> Result<(), QueueError> {
+        let game_roles = self.queue_game_roles(queue);
+        let queue_countdown_message = send_with_retries(|| {
+            queue.channel_id.send_message(
+                &ctx,
+                CreateMessage::new()
+                    .content(build_queue_pings(queue))
+                    .allowed_mentions(CreateAllowedMentions::new().roles(queue.roles.clone()))
+                    .embed(build_queue_embed(queue, &game_roles))
+                    .components(QueueCommand::build_queue_buttons(queue)),
+            )
+        })
+        .await?;
+        queue.message_id = queue_countdown_message.id;
+        if game_queue.discussion_thread {
+            match queue
+                .channel_id
+                .create_thread_from_message(
+                    &ctx,
+                    queue.message_id,
+                    CreateThread::new(format!("{}'s {} Queue", queue.owner_name, queue.game_name))
+                        .auto_archive_duration(AutoArchiveDuration::OneDay),
+                )
+                .await
+            {
+                Ok(thread) => {
+                    if let Err(why) = thread.id.add_thread_member(&ctx, queue.owner_id).await {
+                        println!("Cannot add the owner to the queue thread: {why}");
+                    }
+                    queue.thread_id = Some(thread.id);
+                }
+                Err(why) => println!("Cannot open queue thread: {why}"),
+            }
+        }
+        // Tracked before the event claims its voice channel, so the channel
+        // counts as taken from then on.
+        self.queues
+            .update(|queues| queues.insert(queue.message_id, queue.clone()));
+        if with_event {
+            self.schedule_event(ctx, queue).await;
+        }
+        Ok(())
+    }
+
+    /// The time zone the user set with `/timezone`, if any.
+    fn time_zone(&self, user_id: UserId) -> Option<TimeZone> {
+        self.user_settings.read(|user_settings| {
+            user_settings
+                .get(&user_id)
+                .and_then(|user_settings| user_settings.time_zone())
+        })
+    }
+
+    /// Works out which of the chosen roles can actually be pinged, returning
+    /// the roles to ping and the roles held back along with when they free up.
+    fn take_role_pings(
+        &self,
+        queue_command: &CommandInteraction,
+        roles: &[RoleId],
+        now: u64,
+    ) -> (Vec<RoleId>, Vec<RoleReadyAt>) {
+        let cooldowns = &self.settings.queue_cooldowns;
+        let is_moderator = queue_command.member.as_ref().is_some_and(|member| {
+            member
+                .roles
+                .iter()
+                .any(|role_id| cooldowns.moderator_roles.contains(role_id))
+        });
+        if is_moderator {
+            return (roles.to_vec(), vec![]);
+        }
+
+        self.ping_cooldowns.lock().unwrap().take_pings(
+            cooldowns,
+            queue_command.user.id,
+            queue_command.channel_id,
+            roles,
+            now,
+        )
+    }
+
+    /// Picks up every queue that was still running when the bot last shut down.
+    pub async fn resume(&self, ctx: &Context) {
+        if self.resumed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let queues: Vec<QueueState> = self
+            .queues
+            .read(|queues| queues.values().cloned().collect());
+        for queue in queues {
+            // Only a deleted message ends the queue, Discord being unreachable
+            // for a moment shouldn't.
+            match queue.channel_id.message(ctx, queue.message_id).await {
+                Ok(_) => {}
+                Err(why) => match QueueError::from(why) {
+                    QueueError::MessageDeleted => {
+                        self.message_deleted(ctx, queue.message_id).await;
+                        continue;
+                    }
+                    why => println!("Cannot check on queue {}: {why}", queue.message_id),
+                },
+            }
+
+            let queue_command = self.clone();
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                queue_command.drive(&ctx, queue.message_id).await;
+            });
+        }
+    }
+
+    /// Runs a posted queue from wherever it currently is until it finishes.
+    async fn drive(&self, ctx: &Context, message_id: MessageId) {
+        let mut queue_button_stream = ComponentInteractionCollector::new(&ctx.shard)
+            .message_id(message_id)
+            .stream();
+
+        loop {
+            let Some((wake_at, syncs_event)) = self.queues.read(|queues| {
+                queues.get(&message_id).map(|queue| {
+                    let syncs_event =
+                        queue.scheduled_event_id.is_some() && queue.phase == QueuePhase::Countdown;
+                    (queue.wake_at(), syncs_event)
+                })
+            }) else {
+                return;
+            };
+            let mut sleep_secs = wake_at.saturating_sub(now_secs());
+            if syncs_event {
+                sleep_secs = sleep_secs.min(EVENT_SYNC_SECS);
+            }
+
+            let result = tokio::select! {
+                _ = sleep(Duration::from_secs(sleep_secs)) => {
+                    match self.sync_event_users(ctx, message_id).await {
+                        Ok(true) => self.apply(ctx, message_id, QueueEvent::Timer).await,
+                        synced => synced,
+                    }
+                }
+                Some(interaction) = queue_button_stream.next() => {
+                    let Some(queue) = self.queues.read(|queues| queues.get(&message_id).cloned())
+                    else {
+                        return;
+                    };
+                    self.handle_queue_button(ctx, &queue, &interaction).await
+                }
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(why) => self.handle_queue_error(ctx, message_id, why).await,
+            }
+        }
+    }
+
+    /// Deals with something going wrong in a running queue. A deleted queue
+    /// message cancels the queue, anything else is logged and the queue
+    /// carries on.
+    async fn handle_queue_error(&self, ctx: &Context, message_id: MessageId, why: QueueError) {
+        match why {
+            QueueError::MessageDeleted => self.message_deleted(ctx, message_id).await,
+            why => println!("Queue {message_id} ran into an error: {why}"),
+        }
+    }
+
+    /// Cancels the queue posted in a message that has been deleted, tidying
+    /// up everything else it left behind.
+    pub async fn message_deleted(&self, ctx: &Context, message_id: MessageId) {
+        if let Some(queue) = self.queues.read(|queues| queues.get(&message_id).cloned()) {
+            println!("Queue {message_id} was deleted, cancelling it");
+            self.finish(ctx, &queue, true).await;
+        }
+    }
+
+    /// Feeds an event to the queue's state machine and carries out what it
+    /// asks for in Discord. Returns false once the queue is over.
+    async fn apply(
+        &self,
+        ctx: &Context,
+        message_id: MessageId,
+        event: QueueEvent,
+    ) -> Result<bool, QueueError> {
+        let Some((mut queue, effects)) = self.queues.update(|queues| {
+            let queue = queues.get_mut(&message_id)?;
+            let effects = self.queue_machine(&queue.game_name).handle(queue, event);
+            Some((queue.clone(), effects))
+        }) else {
+            return Ok(false);
+        };
+
+        // Every effect gets its turn, so one failed request doesn't leave the
+        // rest of the queue behind.
+        let mut running = true;
+        let mut first_error = None;
+        for effect in effects {
+            let applied = match effect {
+                QueueEffect::Redraw => self.update_queue_message(ctx, &queue).await,
+                QueueEffect::Joined(user_id) => {
+                    QueueCommand::add_to_thread(ctx, &queue, user_id).await;
+                    Ok(())
+                }
+                QueueEffect::GameStarted { party, first_game } => {
+                    if first_game {
+                        self.start_first_game(ctx, &mut queue, &party).await;
+                    }
+                    self.notify_party(ctx, &queue, &party, "Queue is starting!")
+                        .await
+                }
+                QueueEffect::PartyFull(party) => {
+                    self.ping_party(ctx, &queue, &party, "Next game is full!")
+                        .await
+                }
+                QueueEffect::MatchOver(party) => {
+                    let queue_command = self.clone();
+                    let ctx = ctx.clone();
+                    let queue = queue.clone();
+                    tokio::spawn(async move {
+                        if let Err(why) = queue_command.report_result(&ctx, &queue, &party).await {
+                            println!("Cannot report match result: {why}");
+                        }
+                    });
+                    Ok(())
+                }
+                QueueEffect::Rescheduled => {
+                    events::move_queue_event(ctx, &queue).await;
+                    Ok(())
+                }
+                QueueEffect::Closed { cancelled, waiting } => {
+                    running = false;
+                    let notified = self
+                        .notify_party(
+                            ctx,
+                            &queue,
+                            &waiting,
+                            "match window is over, your next game should be starting!",
+                        )
+                        .await;
+                    self.close(ctx, &queue, cancelled).await;
+                    notified
+                }
+            };
+            if let Err(why) = applied {
+                first_error.get_or_insert(why);
+            }
+        }
+        match first_error {
+            Some(why) => Err(why),
+            None => Ok(running),
+        }
+    }
+
+    fn queue_machine(&self, game_name: &str) -> QueueMachine<SystemClock> {
+        let match_length = self
+            .game_queue(game_name)
+            .map_or(GameQueues::default_match_length(), |game_queue| {
+                self.match_length(game_queue)
+            });
+        QueueMachine::new(
+            SystemClock,
+            match_length,
+            Duration::from_secs(QUEUE_EXTEND_SECS),
+        )
+    }
+
+    /// Maps a press of one of the queue message's buttons onto the queue.
+    /// Returns false once the queue is over.
+    async fn handle_queue_button(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        interaction: &ComponentInteraction,
+    ) -> Result<bool, QueueError> {
+        let message_id = queue.message_id;
+        let custom_id = interaction.data.custom_id.as_str();
+        if OWNER_BUTTONS.contains(&custom_id)
+            && QueueCommand::reject_non_owner(ctx, interaction, queue).await?
+        {
+            return Ok(true);
+        }
+
+        with_retries(|| interaction.create_response(&ctx, CreateInteractionResponse::Acknowledge))
+            .await?;
+        let event = match custom_id {
+            "queue_start_now" => {
+                // Anyone who marked the event interested since the last sync
+                // still makes it into the first game.
+                if !self.sync_event_users(ctx, message_id).await? {
+                    return Ok(false);
+                }
+                QueueEvent::StartNow(queue.phase)
+            }
+            "queue_extend" => QueueEvent::Extend,
+            "queue_cancel" => QueueEvent::Cancel,
+            "queue_make_teams" => {
+                let queue_command = self.clone();
+                let ctx = ctx.clone();
+                let queue = queue.clone();
+                let interaction = interaction.clone();
+                tokio::spawn(async move {
+                    if let Err(why) = queue_command.make_teams(&ctx, &interaction, &queue).await {
+                        println!("Cannot make teams: {why}");
+                    }
+                });
+                return Ok(true);
+            }
+            "queue_ready_check" => {
+                let queue_command = self.clone();
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    queue_command.ready_check(&ctx, message_id).await;
+                });
+                return Ok(true);
+            }
+            _ => QueueEvent::Toggle(interaction.user.id),
+        };
+        self.apply(ctx, message_id, event).await
+    }
+
+    async fn add_to_thread(ctx: &Context, queue: &QueueState, user_id: UserId) {
+        if let Some(thread_id) = queue.thread_id {
+            if let Err(why) = thread_id.add_thread_member(ctx, user_id).await {
+                println!("Cannot add {user_id} to the queue thread: {why}");
+            }
+        }
+    }
+
+    /// Sets up a Scheduled Event for a queue that is a while off, reserving
+    /// the voice channel it points at for the queue.
+    async fn schedule_event(&self, ctx: &Context, queue: &mut QueueState) {
+        let Some(voice_channel_id) = self.claim_voice_channel(ctx, queue).await else {
+            println!("No voice channel free for the queue event");
+            return;
+        };
+        queue.voice_channel_id = Some(voice_channel_id);
+        queue.scheduled_event_id = events::create_queue_event(ctx, queue, voice_channel_id).await;
+        self.queues.update(|queues| {
+            if let Some(stored_queue) = queues.get_mut(&queue.message_id) {
+                stored_queue.scheduled_event_id = queue.scheduled_event_id;
+            }
+        });
+        if queue.scheduled_event_id.is_some() {
+            if let Err(why) = self.update_queue_message(ctx, queue).await {
+                println!("Cannot link the queue event: {why}");
+            }
+        }
+    }
+
+    /// Pulls anyone newly interested in the queue's event into the queue.
+    /// Returns false once the queue is over.
+    async fn sync_event_users(
+        &self,
+        ctx: &Context,
+        message_id: MessageId,
+    ) -> Result<bool, QueueError> {
+        let Some(queue) = self.queues.read(|queues| queues.get(&message_id).cloned()) else {
+            return Ok(false);
+        };
+        if queue.scheduled_event_id.is_none() || queue.phase != QueuePhase::Countdown {
+            return Ok(true);
+        }
+        let interested_users = events::interested_users(ctx, &queue).await;
+        self.apply(
+            ctx,
+            message_id,
+            QueueEvent::EventInterested(interested_users),
+        )
+        .await
+    }
+
+    /// Redraws the queue message and its buttons from the queue's state.
+    async fn update_queue_message(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+    ) -> Result<(), QueueError> {
+        let game_roles = self.queue_game_roles(queue);
+        with_retries(|| {
+            queue.channel_id.edit_message(
+                ctx,
+                queue.message_id,
+                EditMessage::new()
+                    .embed(build_queue_embed(queue, &game_roles))
+                    .components(QueueCommand::build_queue_buttons(queue))
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Tells anyone but the queue owner that a button isn't theirs to press.
+    /// Returns true if the press was rejected.
+    async fn reject_non_owner(
+        ctx: &Context,
+        interaction: &ComponentInteraction,
+        queue: &QueueState,
+    ) -> Result<bool, QueueError> {
+        if interaction.user.id == queue.owner_id {
+            return Ok(false);
+        }
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(format!("Only {} can do that", queue.owner_name)),
+                ),
+            )
+            .await?;
+        Ok(true)
+    }
+
+    /// Posts a split of the waiting players into two teams, which the owner
+    /// can reshuffle, balance, or send off to voice channels.
+    async fn make_teams(
+        &self,
+        ctx: &Context,
+        interaction: &ComponentInteraction,
+        queue: &QueueState,
+    ) -> Result<(), QueueError> {
+        let players = queue.users_waiting.clone();
+        let skill_ratings: HashMap<UserId, u32> = self.user_settings.read(|user_settings| {
+            players
+                .iter()
+                .filter_map(|user_id| {
+                    let skill_rating = user_settings.get(user_id)?.skill_rating?;
+                    Some((*user_id, skill_rating))
+                })
+                .collect()
+        });
+        let mut teams = random_teams(&players);
+
+        let teams_message = interaction
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .embed(build_teams_embed(&queue.game_name, &teams, &skill_ratings))
+                    .components(QueueCommand::build_teams_buttons()),
+            )
+            .await?;
+
+        let mut teams_button_stream = teams_message
+            .await_component_interactions(ctx)
+            .timeout(Duration::from_secs(TEAMS_BUTTON_TIMEOUT_SECS))
+            .stream();
+        while let Some(teams_interaction) = teams_button_stream.next().await {
+            if QueueCommand::reject_non_owner(ctx, &teams_interaction, queue).await? {
+                continue;
+            }
+
+            match teams_interaction.data.custom_id.as_str() {
+                "teams_random" => teams = random_teams(&players),
+                "teams_balanced" => teams = balanced_teams(&players, &skill_ratings),
+                _ => {
+                    teams_interaction
+                        .create_response(&ctx, CreateInteractionResponse::Acknowledge)
+                        .await?;
+                    let report = self.move_teams_to_voice(ctx, queue, &teams).await;
+                    teams_interaction
+                        .create_followup(
+                            &ctx,
+                            CreateInteractionResponseFollowup::new()
+                                .ephemeral(true)
+                                .content(report),
+                        )
+                        .await?;
+                    continue;
+                }
+            }
+            teams_interaction
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new().embed(build_teams_embed(
+                            &queue.game_name,
+                            &teams,
+                            &skill_ratings,
+                        )),
+                    ),
+                )
+                .await?;
+        }
+
+        queue
+            .channel_id
+            .edit_message(ctx, teams_message.id, EditMessage::new().components(vec![]))
+            .await?;
+        Ok(())
+    }
+
+    /// Moves each team into its own voice channel from the guild's voice
+    /// expander category, returning a summary for the owner.
+    async fn move_teams_to_voice(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        teams: &Teams,
+    ) -> String {
+        let Some(guild_id) = queue.guild_id else {
+            return "Teams can only be moved inside a server".to_string();
+        };
+        let Some((category_id, category_settings)) =
+            voice_expander::guild_category(ctx, &self.settings, guild_id).await
+        else {
+            return "This server has no voice channels set up for teams".to_string();
+        };
+        // Held until the teams are in, since moving them in is what stops the
+        // channels looking free to a queue claiming one at the same time.
+        let _voice_claim = self.voice_claims.lock().await;
+        let team_channels = voice_expander::claim_empty_channels(
+            ctx,
+            guild_id,
+            category_id,
+            &category_settings,
+            &self.queue_voice_channels(),
+            teams.len(),
+        )
+        .await;
+        if team_channels.len() < teams.len() {
+            return "There aren't enough free voice channels for both teams".to_string();
+        }
+
+        let mut not_in_voice = MessageBuilder::new();
+        for (team, team_channel) in teams.iter().zip(team_channels.iter()) {
+            for user_id in team {
+                if guild_id
+                    .move_member(ctx, *user_id, team_channel.id)
+                    .await
+                    .is_err()
+                {
+                    not_in_voice.push(" ").mention(user_id);
+                }
+            }
+        }
+        let not_in_voice = not_in_voice.build();
+        if not_in_voice.is_empty() {
+            "Moved both teams to voice".to_string()
+        } else {
+            format!(
+                "Moved both teams, except for players not in voice:{}",
+                not_in_voice
+            )
+        }
+    }
+
+    fn build_teams_buttons() -> Vec<CreateActionRow> {
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("teams_random")
+                .label("Reshuffle")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new("teams_balanced")
+                .label("Balance by Skill")
+                .style(ButtonStyle::Primary),
+            CreateButton::new("teams_move")
+                .label("Move to Voice")
+                .style(ButtonStyle::Success),
+        ])]
+    }
+
+    /// Gets the queue's event and voice channel going along with its first
+    /// game.
+    async fn start_first_game(&self, ctx: &Context, queue: &mut QueueState, party: &[UserId]) {
+        events::start_queue_event(ctx, queue).await;
+        if let Some(voice_channel_id) = self.link_voice_channel(ctx, queue, party).await {
+            queue.voice_channel_id = Some(voice_channel_id);
+            self.queues.update(|queues| {
+                if let Some(stored_queue) = queues.get_mut(&queue.message_id) {
+                    stored_queue.voice_channel_id = Some(voice_channel_id);
+                }
+            });
+        }
+    }
+
+    /// Gives the queue its voice channel, if it doesn't have one from its
+    /// event already, and pulls in any of the party already in voice.
+    async fn link_voice_channel(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        party: &[UserId],
+    ) -> Option<ChannelId> {
+        let guild_id = queue.guild_id?;
+        let voice_channel_id = match queue.voice_channel_id {
+            Some(voice_channel_id) => voice_channel_id,
+            None => {
+                self.game_queue(&queue.game_name)?.voice_channel?;
+                self.claim_voice_channel(ctx, queue).await?
+            }
+        };
+
+        let party_in_voice: Vec<UserId> = ctx
+            .cache
+            .guild(guild_id)
+            .map(|guild| {
+                party
+                    .iter()
+                    .filter(|user_id| {
+                        guild
+                            .voice_states
+                            .get(user_id)
+                            .is_some_and(|voice_state| voice_state.channel_id.is_some())
+                    })
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default();
+        for user_id in party_in_voice {
+            if let Err(why) = guild_id.move_member(ctx, user_id, voice_channel_id).await {
+                println!("Cannot move {user_id} into the queue voice channel: {why}");
+            }
+        }
+        Some(voice_channel_id)
+    }
+
+    /// Claims a voice channel from the voice expander for the queue, named
+    /// after its owner or game, and reserves it for the queue straight away.
+    async fn claim_voice_channel(&self, ctx: &Context, queue: &QueueState) -> Option<ChannelId> {
+        let channel_naming = self
+            .game_queue(&queue.game_name)?
+            .voice_channel
+            .unwrap_or(QueueVoiceChannelName::Owner);
+        let guild_id = queue.guild_id?;
+        let (category_id, category_settings) =
+            voice_expander::guild_category(ctx, &self.settings, guild_id).await?;
+        // Two queues claiming at once would otherwise both see the same
+        // channel as free.
+        let _voice_claim = self.voice_claims.lock().await;
+        let mut voice_channel = voice_expander::claim_empty_channels(
+            ctx,
+            guild_id,
+            category_id,
+            &category_settings,
+            &self.queue_voice_channels(),
+            1,
+        )
+        .await
+        .pop()?;
+        self.queues.update(|queues| {
+            if let Some(stored_queue) = queues.get_mut(&queue.message_id) {
+                stored_queue.voice_channel_id = Some(voice_channel.id);
+                stored_queue.voice_channel_name = Some(voice_channel.name.clone());
+            }
+        });
+
+        let channel_name = match channel_naming {
+            QueueVoiceChannelName::Owner => format!("{}'s {}", queue.owner_name, queue.game_name),
+            QueueVoiceChannelName::Game => queue.game_name.clone(),
+        };
+        if let Err(why) = voice_channel
+            .edit(ctx, EditChannel::new().name(channel_name))
+            .await
+        {
+            println!("Cannot rename queue voice channel: {why}");
+        }
+        Some(voice_channel.id)
+    }
+
+    /// Voice channels that belong to a running queue, which the voice expander
+    /// should leave alone even while they are empty.
+    pub fn queue_voice_channels(&self) -> Vec<ChannelId> {
+        self.queues.read(|queues| {
+            queues
+                .values()
+                .filter_map(|queue| queue.voice_channel_id)
+                .collect()
+        })
+    }
+
+    /// Lets the party know their game is starting, by DM where they allow it
+    /// and with a ping in the queue channel otherwise.
+    async fn notify_party(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        party: &[UserId],
+        text: &str,
+    ) -> Result<(), QueueError> {
+        let (mut ping_users, dm_users): (Vec<UserId>, Vec<UserId>) =
+            party.iter().partition(|user_id| {
+                self.user_settings.read(|user_settings| {
+                    user_settings
+                        .get(user_id)
+                        .is_some_and(|user_settings| user_settings.no_queue_dms)
+                })
+            });
+
+        let dm_content = format!(
+            "{} {} {}",
+            queue.game_name,
+            text,
+            queue.message_id.link(queue.channel_id, queue.guild_id)
+        );
+        // Sent all at once, so a big party doesn't hold up the queue.
+        let dm_results = join_all(dm_users.iter().map(|user_id| async {
+            let dm_channel = user_id.create_dm_channel(ctx).await?;
+            dm_channel
+                .send_message(ctx, CreateMessage::new().content(&dm_content))
+                .await
+        }))
+        .await;
+        for (user_id, dm_result) in dm_users.into_iter().zip(dm_results) {
+            if let Err(why) = dm_result {
+                println!("Cannot DM {user_id}, pinging instead: {why}");
+                ping_users.push(user_id);
+            }
+        }
+        self.ping_party(ctx, queue, &ping_users, text).await
+    }
+
+    async fn ping_party(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        party: &[UserId],
+        text: &str,
+    ) -> Result<(), QueueError> {
+        if party.is_empty() {
+            return Ok(());
+        }
+        let mut ping_message = MessageBuilder::new();
+        ping_message.push(format!("{} {}", queue.game_name, text));
+        party.iter().for_each(|user_id| {
+            ping_message.push(" ").mention(user_id);
+        });
+        let ping_message = ping_message.build();
+        send_with_retries(|| {
+            queue.channel_id.send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(ping_message.clone())
+                    .reference_message((queue.channel_id, queue.message_id)),
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn close(&self, ctx: &Context, queue: &QueueState, cancelled: bool) {
+        let game_roles = self.queue_game_roles(queue);
+        let closed = with_retries(|| {
+            queue.channel_id.edit_message(
+                ctx,
+                queue.message_id,
+                EditMessage::new()
+                    .embed(build_closed_queue_embed(queue, &game_roles, cancelled))
+                    .components(vec![])
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+        })
+        .await;
+        match closed {
+            Ok(_) | Err(QueueError::MessageDeleted) => {}
+            Err(why) => println!("Cannot close queue message: {why}"),
+        }
+        self.finish(ctx, queue, cancelled).await;
+    }
+
+    /// Cleans up everything a queue left behind besides its message and
+    /// records it in the history.
+    /// Only the first caller for a queue does anything, since a deleted
+    /// message can be noticed by its drive loop and the gateway at once.
+    async fn finish(&self, ctx: &Context, queue: &QueueState, cancelled: bool) {
+        let Some(stored_queue) = self
+            .queues
+            .update(|queues| queues.remove(&queue.message_id))
+        else {
+            return;
+        };
+        // The voice channel may have been claimed after `queue` was read.
+        if let (Some(voice_channel_id), Some(voice_channel_name)) = (
+            stored_queue.voice_channel_id,
+            stored_queue.voice_channel_name.clone(),
+        ) {
+            if let Err(why) = voice_channel_id
+                .edit(ctx, EditChannel::new().name(voice_channel_name))
+                .await
+            {
+                println!("Cannot rename queue voice channel back: {why}");
+            }
+        }
+        if let Some(thread_id) = queue.thread_id {
+            if let Err(why) = thread_id
+                .edit_thread(ctx, EditThread::new().archived(true))
+                .await
+            {
+                println!("Cannot archive queue thread: {why}");
+            }
+        }
+        events::end_queue_event(ctx, queue).await;
+        let role_joins = QueueCommand::count_role_joins(ctx, &stored_queue).await;
+        self.history.update(|history| {
+            history.push(QueueRecord::new(
+                &stored_queue,
+                role_joins,
+                now_secs(),
+                cancelled,
+            ))
+        });
+    }
+
+    /// Counts how many of the people who joined have each of the pinged
+    /// roles, so `/queue stats` can tell which roles turn up.
+    async fn count_role_joins(ctx: &Context, queue: &QueueState) -> HashMap<RoleId, usize> {
+        let mut role_joins: HashMap<RoleId, usize> = HashMap::new();
+        let Some(guild_id) = queue.guild_id else {
+            return role_joins;
+        };
+        for user_id in &queue.joined_users {
+            let member = match guild_id.member(ctx, *user_id).await {
+                Ok(member) => member,
+                Err(why) => {
+                    println!("Cannot look up the roles of {user_id}: {why}");
+                    continue;
+                }
+            };
+            queue
+                .roles
+                .iter()
+                .filter(|role_id| member.roles.contains(role_id))
+                .for_each(|role_id| *role_joins.entry(*role_id).or_default() += 1);
+        }
+        role_joins
+    }
+
+    fn build_queue_buttons(queue: &QueueState) -> Vec<CreateActionRow> {
+        let join_button = match queue.phase {
+            QueuePhase::Countdown => CreateButton::new("join_queue").label("Toggle Join Queue"),
+            QueuePhase::Open => CreateButton::new("wait_for_me").label("Toggle Join Next Game"),
+        };
+        let mut join_row = vec![join_button.style(ButtonStyle::Success)];
+        if queue.users_waiting.len() >= 2 {
+            join_row.push(
+                CreateButton::new("queue_make_teams")
+                    .label("Make Teams")
+                    .style(ButtonStyle::Secondary),
+            );
+        }
+        let mut owner_row = vec![
+            CreateButton::new("queue_start_now")
+                .label(match queue.phase {
+                    QueuePhase::Countdown => "Start Now",
+                    QueuePhase::Open => "We're Back",
+                })
+                .style(ButtonStyle::Primary),
+            CreateButton::new("queue_extend")
+                .label(format!(
+                    "+{}",
+                    format_duration(Duration::from_secs(QUEUE_EXTEND_SECS))
+                ))
+                .style(ButtonStyle::Secondary),
+            CreateButton::new("queue_cancel")
+                .label("Cancel")
+                .style(ButtonStyle::Danger),
+        ];
+        if !queue.users_waiting.is_empty() && !queue.ready_check_running {
+            owner_row.insert(
+                1,
+                CreateButton::new("queue_ready_check")
+                    .label("Ready Check")
+                    .style(ButtonStyle::Secondary),
+            );
+        }
+        vec![
+            CreateActionRow::Buttons(join_row),
+            CreateActionRow::Buttons(owner_row),
+        ]
+    }
+
+    fn queue_game_roles(&self, queue: &QueueState) -> Vec<ReactionRole> {
+        self.game_queue(&queue.game_name)
+            .map(|game_queue| self.game_queue_roles(game_queue))
+            .unwrap_or_default()
+    }
+
+    /// Shows a select menu in the ephemeral reply and waits for a pick, cleaning
+    /// the reply up if nobody answers.
+    async fn ask(
+        ctx: &Context,
+        queue_command: &CommandInteraction,
+        select_menu: CreateSelectMenu,
+    ) -> Result<Option<Vec<String>>, QueueError> {
+        let select_menu_interaction = queue_command
+            .edit_response(
+                &ctx,
+                EditInteractionResponse::new()
+                    .components(vec![CreateActionRow::SelectMenu(select_menu)]),
+            )
+            .await?
+            .await_component_interaction(&ctx.shard)
+            .timeout(Duration::from_secs(60 * 2))
+            .await;
+        let Some(select_menu_interaction) = select_menu_interaction else {
+            queue_command.delete_response(&ctx).await?;
+            return Ok(None);
+        };
+
+        select_menu_interaction
+            .create_response(&ctx, CreateInteractionResponse::Acknowledge)
+            .await?;
+        match select_menu_interaction.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => Ok(Some(values)),
+            _ => Ok(None),
+        }
+    }
+
+    fn build_selection_menu(
+        &self,
+        menu: &SelectionMenu,
+        game_roles: &HashMap<String, Vec<ReactionRole>>,
+    ) -> CreateSelectMenu {
+        match menu {
+            SelectionMenu::Game { default_game } => CreateSelectMenu::new(
+                "game_select",
+                CreateSelectMenuKind::String {
+                    options: self
+                        .settings
+                        .game_queue
+                        .iter()
+                        .map(|game_queue| {
+                            CreateSelectMenuOption::new(
+                                game_queue.game_name.clone(),
+                                game_queue.game_name.clone(),
+                            )
+                            .default_selection(default_game.as_ref() == Some(&game_queue.game_name))
+                        })
+                        .collect(),
+                },
+            )
+            .placeholder("Game"),
+            SelectionMenu::Timer {
+                game_name,
+                default_wait_secs,
+            } => QueueCommand::build_queue_timer_menu(
+                self.game_queue(game_name)
+                    .expect("Selections are for configured games"),
+                *default_wait_secs,
+            ),
+            SelectionMenu::Roles {
+                game_name,
+                default_roles,
+            } => QueueCommand::build_ping_roles_menu(
+                game_roles.get(game_name).map_or(&[], Vec::as_slice),
+                default_roles,
+            ),
+        }
+    }
+
+    fn build_queue_timer_menu(
+        game_queue: &GameQueues,
+        last_wait_secs: Option<u64>,
+    ) -> CreateSelectMenu {
+        CreateSelectMenu::new(
+            "minute_wait",
+            CreateSelectMenuKind::String {
+                options: game_queue
+                    .wait_times
+                    .iter()
+                    .map(|wait_time| {
+                        CreateSelectMenuOption::new(
+                            format_duration(*wait_time),
+                            wait_time.as_secs().to_string(),
+                        )
+                        .default_selection(last_wait_secs == Some(wait_time.as_secs()))
+                    })
+                    .collect(),
+            },
+        )
+        .placeholder("Queue Timer")
+    }
+
+    fn build_ping_roles_menu(
+        game_roles: &[ReactionRole],
+        last_roles: &[RoleId],
+    ) -> CreateSelectMenu {
+        CreateSelectMenu::new(
+            "ping_roles",
+            CreateSelectMenuKind::String {
+                options: game_roles
+                    .iter()
+                    .map(|reaction_role| {
+                        let role_option = if let Some(emoji_id) = reaction_role.emoji_id {
+                            CreateSelectMenuOption::new(
+                                reaction_role.title.clone(),
+                                reaction_role.role_id.to_string(),
+                            )
+                            .emoji(ReactionType::Custom {
+                                animated: false,
+                                id: emoji_id,
+                                name: None,
+                            })
+                        } else {
+                            CreateSelectMenuOption::new(
+                                reaction_role.title.clone(),
+                                reaction_role.role_id.to_string(),
+                            )
+                            .emoji(ReactionType::Unicode(
+                                reaction_role.emoji_char.clone().unwrap(),
+                            ))
+                        };
+                        role_option.default_selection(last_roles.contains(&reaction_role.role_id))
+                    })
+                    .collect(),
+            },
+        )
+        .min_values(1)
+        .max_values(game_roles.len() as u8)
+        .placeholder("Roles to Ping")
+    }
+
+    /// Looks up an option of the chosen `/queue` subcommand.
+    fn command_option<'a>(
+        queue_command: &'a CommandInteraction,
+        name: &str,
+    ) -> Option<&'a CommandDataOptionValue> {
+        let options = match queue_command
+            .data
+            .options
+            .first()
+            .map(|option| &option.value)
+        {
+            Some(CommandDataOptionValue::SubCommand(options)) => options,
+            _ => &queue_command.data.options,
+        };
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .map(|option| &option.value)
+    }
+
+    fn string_option<'a>(queue_command: &'a CommandInteraction, name: &str) -> Option<&'a str> {
+        QueueCommand::command_option(queue_command, name).and_then(|value| value.as_str())
+    }
+
+    fn game_queue(&self, game_name: &str) -> Option<&GameQueues> {
+        self.settings
+            .game_queue
+            .iter()
+            .find(|game_queue| game_queue.game_name == game_name)
+    }
+
+    fn game_queue_roles(&self, game_queue: &GameQueues) -> Vec<ReactionRole> {
+        self.settings
+            .message_id_to_emoji_reaction_to_reactionrole_lookup()
+            .get(&game_queue.roles_message_id)
+            .expect("Queue Roles must be Reaction Roles too!")
+            .values()
+            .filter(|reaction_role| !game_queue.exclude.contains(&reaction_role.title))
+            .cloned()
+            .collect()
+    }
+
+    pub fn new(
+        settings: Settings,
+        user_settings: UserSettingsStore,
+        match_results: MatchResultsStore,
+    ) -> QueueCommand {
+        QueueCommand {
+            settings,
+            queues: Store::open("queues.json"),
+            resumed: Arc::new(AtomicBool::new(false)),
+            scheduler_started: Arc::new(AtomicBool::new(false)),
+            ping_cooldowns: Arc::new(Mutex::new(PingCooldowns::default())),
+            game_sightings: Arc::new(Mutex::new(GameSightings::default())),
+            user_settings,
+            history: Store::open("queue_history.json"),
+            match_results,
+            match_lengths: Store::open("match_lengths.json"),
+            voice_sessions: Arc::new(Mutex::new(HashMap::new())),
+            voice_claims: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    pub fn register(&self) -> CreateCommand {
+        let game_option = |description: &str| {
+            self.settings.game_queue.iter().fold(
+                CreateCommandOption::new(CommandOptionType::String, "game", description)
+                    .required(false),
+                |game_option, game_queue| {
+                    game_option.add_string_choice(&game_queue.game_name, &game_queue.game_name)
+                },
+            )
+        };
+        CreateCommand::new("queue")
+            .description("Start a queue or see how queues have been going")
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "start",
+                    "Ping the LFG roles for a game and start a queue",
+                )
+                .add_sub_option(game_option("Game to queue for"))
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "party_size",
+                        "Players needed, overrides the game's usual party size",
+                    )
+                    .min_int_value(2)
+                    .max_int_value(100)
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "in",
+                        "How long until the queue starts, like 10m or 1h30m",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "at",
+                        "When the queue starts in your /timezone, like 9pm or tomorrow 20:30",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "roles",
+                        "Roles to ping, as mentions or names separated by commas",
+                    )
+                    .required(false),
+                ),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "again",
+                "Start a queue with the same choices as your last one",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "See every queue running right now and join or leave them",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "skill",
+                    "Set your skill rating, used to balance teams",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "rating", "From 1 to 10")
+                        .min_int_value(1)
+                        .max_int_value(10)
+                        .required(true),
+                ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "stats",
+                    "Busiest hours, top queue starters and which roles turn up",
+                )
+                .add_sub_option(game_option("Only count queues for this game")),
+            )
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use serenity::builder::*;
+use serenity::futures::StreamExt;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use super::error::send_with_retries;
+use super::machine::QueueEvent;
+use super::QueueCommand;
+use crate::clock_time::now_secs;
+use crate::settings::GameQueues;
+
+impl QueueCommand {
+    /// Pings everyone lined up for the next game to check they're still
+    /// around. Anyone who isn't ready in time is dropped, and the waitlist
+    /// moves up into their spots.
+    pub(super) async fn ready_check(&self, ctx: &Context, message_id: MessageId) {
+        let Some(queue) = self.queues.update(|queues| {
+            let queue = queues.get_mut(&message_id)?;
+            if queue.ready_check_running || queue.users_waiting.is_empty() {
+                return None;
+            }
+            queue.ready_check_running = true;
+            Some(queue.clone())
+        }) else {
+            return;
+        };
+        if let Err(why) = self.update_queue_message(ctx, &queue).await {
+            println!("Cannot show the ready check on the queue: {why}");
+        }
+
+        let timeout = self
+            .game_queue(&queue.game_name)
+            .map_or(GameQueues::default_ready_check_timeout(), |game_queue| {
+                game_queue.ready_check_timeout
+            });
+        let ends_at = now_secs() + timeout.as_secs();
+        let checked_users = queue.users_waiting.clone();
+        let mut answers: HashMap<UserId, bool> = HashMap::new();
+
+        let ready_check_message = match send_with_retries(|| {
+            queue.channel_id.send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(QueueCommand::build_ready_check_message(
+                        &queue.game_name,
+                        &checked_users,
+                        &answers,
+                        ends_at,
+                    ))
+                    .components(QueueCommand::build_ready_check_buttons())
+                    .reference_message((queue.channel_id, message_id)),
+            )
+        })
+        .await
+        {
+            Ok(ready_check_message) => ready_check_message,
+            Err(why) => {
+                println!("Cannot send ready check: {why}");
+                self.queues.update(|queues| {
+                    if let Some(queue) = queues.get_mut(&message_id) {
+                        queue.ready_check_running = false;
+                    }
+                });
+                return;
+            }
+        };
+
+        let mut ready_check_stream = ready_check_message
+            .await_component_interactions(ctx)
+            .timeout(timeout)
+            .stream();
+        while let Some(ready_interaction) = ready_check_stream.next().await {
+            let user_id = ready_interaction.user.id;
+            if !checked_users.contains(&user_id) {
+                if let Err(why) = ready_interaction
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .content("This ready check isn't for you"),
+                        ),
+                    )
+                    .await
+                {
+                    println!("Cannot turn away a ready check answer: {why}");
+                }
+                continue;
+            }
+
+            answers.insert(
+                user_id,
+                ready_interaction.data.custom_id == "ready_check_ready",
+            );
+            if let Err(why) = ready_interaction
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new().content(
+                            QueueCommand::build_ready_check_message(
+                                &queue.game_name,
+                                &checked_users,
+                                &answers,
+                                ends_at,
+                            ),
+                        ),
+                    ),
+                )
+                .await
+            {
+                println!("Cannot update the ready check: {why}");
+            }
+            if answers.len() == checked_users.len() {
+                break;
+            }
+        }
+
+        let dropped_users: Vec<UserId> = checked_users
+            .iter()
+            .filter(|user_id| answers.get(user_id) != Some(&true))
+            .copied()
+            .collect();
+        if let Err(why) = self
+            .apply(
+                ctx,
+                message_id,
+                QueueEvent::ReadyCheckOver {
+                    dropped: dropped_users.clone(),
+                },
+            )
+            .await
+        {
+            self.handle_queue_error(ctx, message_id, why).await;
+        }
+        // The queue may have ended while the check was running.
+        let remaining_queue = self.queues.read(|queues| queues.get(&message_id).cloned());
+
+        let mut roster_message = MessageBuilder::new();
+        roster_message.push_line(format!("## {} Ready Check Over", queue.game_name));
+        if let Some(remaining_queue) = &remaining_queue {
+            roster_message.push_line("### Next Game");
+            remaining_queue.users_waiting.iter().for_each(|user_id| {
+                roster_message.mention(user_id).push(" ");
+            });
+            roster_message.push_line("");
+        }
+        if !dropped_users.is_empty() {
+            roster_message.push_line("### Dropped");
+            dropped_users.iter().for_each(|user_id| {
+                roster_message.mention(user_id).push(" ");
+            });
+        }
+        if let Err(why) = queue
+            .channel_id
+            .edit_message(
+                ctx,
+                ready_check_message.id,
+                EditMessage::new()
+                    .content(roster_message.build())
+                    .components(vec![]),
+            )
+            .await
+        {
+            println!("Cannot post the ready check roster: {why}");
+        }
+    }
+
+    fn build_ready_check_message(
+        game_name: &str,
+        checked_users: &[UserId],
+        answers: &HashMap<UserId, bool>,
+        ends_at: u64,
+    ) -> String {
+        let mut ready_check_message = MessageBuilder::new();
+        ready_check_message
+            .push_line(format!("## {} Ready Check", game_name))
+            .push_line(format!(
+                "Anyone not ready <t:{}:R> gives up their spot",
+                ends_at
+            ));
+        checked_users.iter().for_each(|user_id| {
+            let answer = match answers.get(user_id) {
+                Some(true) => "✅",
+                Some(false) => "❌",
+                None => "⏳",
+            };
+            ready_check_message
+                .push_line("")
+                .push(format!("{} ", answer))
+                .mention(user_id);
+        });
+        ready_check_message.build()
+    }
+
+    fn build_ready_check_buttons() -> Vec<CreateActionRow> {
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("ready_check_ready")
+                .label("Ready")
+                .style(ButtonStyle::Success),
+            CreateButton::new("ready_check_not_ready")
+                .label("Not Ready")
+                .style(ButtonStyle::Danger),
+        ])]
+    }
+}
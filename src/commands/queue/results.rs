@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use serenity::builder::*;
+use serenity::futures::StreamExt;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use super::error::{send_with_retries, QueueError};
+use super::state::QueueState;
+use super::QueueCommand;
+use crate::clock_time::now_secs;
+use crate::match_results::MatchResult;
+
+/// How long the owner has to report how a game went.
+const RESULT_REPORT_TIMEOUT_SECS: u64 = 60 * 60;
+
+impl QueueCommand {
+    /// Asks the queue owner whether the party won, and who the MVPs were,
+    /// then saves the result for `/leaderboard`.
+    pub(super) async fn report_result(
+        &self,
+        ctx: &Context,
+        queue: &QueueState,
+        party: &[UserId],
+    ) -> Result<(), QueueError> {
+        // Nobody can answer for a queue the bot runs itself.
+        if queue.owner_id == ctx.cache.current_user().id {
+            return Ok(());
+        }
+
+        let report_message = send_with_retries(|| {
+            queue.channel_id.send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(QueueCommand::build_result_prompt(queue, party))
+                    .components(QueueCommand::build_result_components(party))
+                    .allowed_mentions(CreateAllowedMentions::new())
+                    .reference_message((queue.channel_id, queue.message_id)),
+            )
+        })
+        .await?;
+
+        let mut mvps: Vec<UserId> = vec![];
+        let mut won = None;
+        let mut report_stream = report_message
+            .await_component_interactions(ctx)
+            .timeout(Duration::from_secs(RESULT_REPORT_TIMEOUT_SECS))
+            .stream();
+        while let Some(report_interaction) = report_stream.next().await {
+            if QueueCommand::reject_non_owner(ctx, &report_interaction, queue).await? {
+                continue;
+            }
+            report_interaction
+                .create_response(&ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            match &report_interaction.data.kind {
+                ComponentInteractionDataKind::UserSelect { values } => {
+                    // Only the party can be MVP of their own game.
+                    mvps = values
+                        .iter()
+                        .filter(|user_id| party.contains(user_id))
+                        .copied()
+                        .collect();
+                }
+                _ => {
+                    won = Some(report_interaction.data.custom_id == "result_win");
+                    break;
+                }
+            }
+        }
+
+        if let Some(won) = won {
+            self.match_results.update(|match_results| {
+                match_results.push(MatchResult {
+                    game_name: queue.game_name.clone(),
+                    guild_id: queue.guild_id,
+                    players: party.to_vec(),
+                    won,
+                    mvps: mvps.clone(),
+                    reported_at: now_secs(),
+                })
+            });
+        }
+        queue
+            .channel_id
+            .edit_message(
+                ctx,
+                report_message.id,
+                EditMessage::new()
+                    .content(QueueCommand::build_result_summary(queue, party, won, &mvps))
+                    .components(vec![]),
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn build_result_prompt(queue: &QueueState, party: &[UserId]) -> String {
+        let mut result_prompt = MessageBuilder::new();
+        result_prompt.push_line(format!("## {} Match Result", queue.game_name));
+        party.iter().for_each(|user_id| {
+            result_prompt.mention(user_id).push(" ");
+        });
+        result_prompt
+            .push_line("")
+            .push(format!("{}, how did it go?", queue.owner_name));
+        result_prompt.build()
+    }
+
+    fn build_result_summary(
+        queue: &QueueState,
+        party: &[UserId],
+        won: Option<bool>,
+        mvps: &[UserId],
+    ) -> String {
+        let mut result_summary = MessageBuilder::new();
+        result_summary.push_line(format!("## {} Match Result", queue.game_name));
+        party.iter().for_each(|user_id| {
+            result_summary.mention(user_id).push(" ");
+        });
+        result_summary.push_line("");
+        match won {
+            Some(true) => result_summary.push_line("**Win**"),
+            Some(false) => result_summary.push_line("**Loss**"),
+            None => result_summary.push_line("No result reported"),
+        };
+        if !mvps.is_empty() {
+            result_summary.push("MVP");
+            mvps.iter().for_each(|user_id| {
+                result_summary.push(" ").mention(user_id);
+            });
+        }
+        result_summary.build()
+    }
+
+    fn build_result_components(party: &[UserId]) -> Vec<CreateActionRow> {
+        vec![
+            CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    "result_mvps",
+                    CreateSelectMenuKind::User {
+                        default_users: None,
+                    },
+                )
+                .placeholder("MVPs (optional)")
+                .min_values(0)
+                .max_values(party.len().clamp(1, 25) as u8),
+            ),
+            CreateActionRow::Buttons(vec![
+                CreateButton::new("result_win")
+                    .label("Win")
+                    .style(ButtonStyle::Success),
+                CreateButton::new("result_loss")
+                    .label("Loss")
+                    .style(ButtonStyle::Danger),
+            ]),
+        ]
+    }
+}
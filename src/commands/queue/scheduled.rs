@@ -0,0 +1,112 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tokio::time::sleep;
+
+use super::error::QueueError;
+use super::state::QueueState;
+use super::QueueCommand;
+use crate::clock_time::now_secs;
+use crate::settings::ScheduledQueue;
+
+impl QueueCommand {
+    /// Starts posting every `[[scheduled_queue]]` as it comes around.
+    pub fn start_scheduler(&self, ctx: &Context) {
+        if self.scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        for scheduled_queue in self.settings.scheduled_queue.clone() {
+            let queue_command = self.clone();
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                queue_command
+                    .run_scheduled_queue(&ctx, &scheduled_queue)
+                    .await;
+            });
+        }
+    }
+
+    async fn run_scheduled_queue(&self, ctx: &Context, scheduled_queue: &ScheduledQueue) {
+        while let Some(queue_at) = scheduled_queue.next_start(now_secs()) {
+            let post_at = queue_at.saturating_sub(scheduled_queue.post_before.as_secs());
+            sleep(Duration::from_secs(post_at.saturating_sub(now_secs()))).await;
+
+            // Coming back up after a restart, this week's queue may be out already.
+            let already_posted = self.queues.read(|queues| {
+                queues.values().any(|queue| {
+                    queue.channel_id == scheduled_queue.channel_id
+                        && queue.scheduled_for == Some(queue_at)
+                })
+            });
+            if !already_posted {
+                match self
+                    .post_scheduled_queue(ctx, scheduled_queue, queue_at)
+                    .await
+                {
+                    Ok(message_id) => {
+                        let queue_command = self.clone();
+                        let ctx = ctx.clone();
+                        tokio::spawn(async move {
+                            queue_command.drive(&ctx, message_id).await;
+                        });
+                    }
+                    Err(why) => println!("Cannot post scheduled queue: {why}"),
+                }
+            }
+
+            sleep(Duration::from_secs(queue_at.saturating_sub(now_secs()))).await;
+        }
+    }
+
+    async fn post_scheduled_queue(
+        &self,
+        ctx: &Context,
+        scheduled_queue: &ScheduledQueue,
+        queue_at: u64,
+    ) -> Result<MessageId, QueueError> {
+        let game_queue = self
+            .game_queue(&scheduled_queue.game_name)
+            .expect("Scheduled queues are checked against the game queues");
+        let guild_id = scheduled_queue
+            .channel_id
+            .to_channel(ctx)
+            .await?
+            .guild()
+            .map(|channel| channel.guild_id);
+        let (owner, owner_name) = match scheduled_queue.owner_id {
+            Some(owner_id) => {
+                let owner = owner_id.to_user(ctx).await?;
+                let owner_name = owner.global_name.clone().unwrap_or(owner.name.clone());
+                (owner, owner_name)
+            }
+            None => (
+                ctx.cache.current_user().clone().into(),
+                self.settings.general.name.clone(),
+            ),
+        };
+
+        let mut queue = QueueState {
+            game_name: game_queue.game_name.clone(),
+            owner_id: owner.id,
+            owner_name,
+            owner_avatar_url: Some(owner.face()),
+            guild_id,
+            channel_id: scheduled_queue.channel_id,
+            roles: scheduled_queue.roles.clone(),
+            started_at: now_secs(),
+            queue_at,
+            scheduled_for: Some(queue_at),
+            match_at: queue_at + self.match_length(game_queue).as_secs(),
+            party_size: scheduled_queue.party_size.or(game_queue.party_size),
+            ..Default::default()
+        };
+        // Like a queue started with `at`, a game night is set for a clock time.
+        let with_event = game_queue.scheduled_event_after.is_some();
+        self.post_queue(ctx, &mut queue, game_queue, with_event)
+            .await?;
+        Ok(queue.message_id)
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serenity::model::prelude::*;
+use serenity::utils::{parse_role_mention, MessageBuilder};
+
+use super::machine::Clock;
+use crate::clock_time::{resolve_clock_time, TimeZone};
+use crate::duration::{format_duration, parse_duration};
+use crate::settings::{GameQueues, ReactionRole};
+use crate::user_settings::QueueChoices;
+
+/// The furthest ahead `in` can set a queue, anything later is what `at` is for.
+const MAX_QUEUE_WAIT_SECS: u64 = 24 * 60 * 60;
+
+/// What `/queue start` was told up front, and about whoever used it.
+#[derive(Debug, Clone, Default)]
+pub struct QueueRequest {
+    pub game: Option<String>,
+    /// The `in` option.
+    pub wait: Option<String>,
+    /// The `at` option.
+    pub at_time: Option<String>,
+    pub roles: Option<String>,
+    pub party_size: Option<usize>,
+    pub channel_id: ChannelId,
+    /// The roles of the member starting the queue, None outside a server.
+    pub member_roles: Option<Vec<RoleId>>,
+    pub time_zone: Option<TimeZone>,
+    pub last_queue: Option<QueueChoices>,
+}
+
+/// A menu the user has to pick from before the queue can be posted, with
+/// what to have picked already.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionMenu {
+    Game {
+        default_game: Option<String>,
+    },
+    Timer {
+        game_name: String,
+        default_wait_secs: Option<u64>,
+    },
+    Roles {
+        game_name: String,
+        default_roles: Vec<RoleId>,
+    },
+}
+
+/// What the selection needs next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionStep {
+    Ask(SelectionMenu),
+    Done(QueueChoices),
+}
+
+/// Works out what a new queue is for, from the command's options and
+/// whatever the user picks in the menus for the rest, without anything
+/// Discord in the way.
+pub struct QueueSelection<'a, C> {
+    clock: C,
+    game_queues: &'a [GameQueues],
+    game_roles: &'a HashMap<String, Vec<ReactionRole>>,
+    request: QueueRequest,
+    game_name: Option<String>,
+    wait_secs: Option<u64>,
+    roles: Option<Vec<RoleId>>,
+}
+
+impl<'a, C: Clock> QueueSelection<'a, C> {
+    pub fn new(
+        clock: C,
+        game_queues: &'a [GameQueues],
+        game_roles: &'a HashMap<String, Vec<ReactionRole>>,
+        request: QueueRequest,
+    ) -> Self {
+        QueueSelection {
+            clock,
+            game_queues,
+            game_roles,
+            request,
+            game_name: None,
+            wait_secs: None,
+            roles: None,
+        }
+    }
+
+    /// Takes in as much as the command's options cover, returning the next
+    /// menu to ask or the finished choices. An error explains to the user
+    /// why the queue can't go ahead.
+    pub fn next(&mut self) -> Result<SelectionStep, String> {
+        let game_queue = match self.game_queue() {
+            Some(game_queue) => game_queue,
+            None => match (&self.request.game, self.game_queues) {
+                (Some(game_name), _) => self
+                    .find_game_queue(game_name)
+                    .ok_or(format!("There's no queue for {}", game_name))?,
+                (None, [game_queue]) => game_queue,
+                (None, _) => {
+                    return Ok(SelectionStep::Ask(SelectionMenu::Game {
+                        default_game: self
+                            .request
+                            .last_queue
+                            .as_ref()
+                            .map(|last_queue| last_queue.game_name.clone()),
+                    }))
+                }
+            },
+        };
+        if self.game_name.is_none() {
+            check_queue_allowed(
+                game_queue,
+                self.request.member_roles.as_deref(),
+                self.request.channel_id,
+            )?;
+            self.game_name = Some(game_queue.game_name.clone());
+        }
+        // Only carry over choices made for the same game.
+        let last_queue = self
+            .request
+            .last_queue
+            .as_ref()
+            .filter(|last_queue| last_queue.game_name == game_queue.game_name);
+
+        let wait_secs = match self.wait_secs {
+            Some(wait_secs) => wait_secs,
+            None => {
+                let wait_secs = match (&self.request.wait, &self.request.at_time) {
+                    (Some(_), Some(_)) => return Err("Pick either in or at, not both".to_string()),
+                    (Some(wait), None) => parse_wait(wait)?,
+                    (None, Some(at_time)) => {
+                        let now = self.clock.now();
+                        resolve_at_time(at_time, self.request.time_zone, now)?.saturating_sub(now)
+                    }
+                    (None, None) => {
+                        return Ok(SelectionStep::Ask(SelectionMenu::Timer {
+                            game_name: game_queue.game_name.clone(),
+                            default_wait_secs: last_queue.map(|last_queue| last_queue.wait_secs),
+                        }))
+                    }
+                };
+                self.wait_secs = Some(wait_secs);
+                wait_secs
+            }
+        };
+
+        let roles = match &self.roles {
+            Some(roles) => roles.clone(),
+            None => match &self.request.roles {
+                Some(roles) => {
+                    let roles = parse_roles(roles, &self.roles_for(game_queue))?;
+                    self.roles = Some(roles.clone());
+                    roles
+                }
+                None => {
+                    return Ok(SelectionStep::Ask(SelectionMenu::Roles {
+                        game_name: game_queue.game_name.clone(),
+                        default_roles: last_queue
+                            .map(|last_queue| last_queue.roles.clone())
+                            .unwrap_or_default(),
+                    }))
+                }
+            },
+        };
+
+        Ok(SelectionStep::Done(QueueChoices {
+            game_name: game_queue.game_name.clone(),
+            wait_secs,
+            roles,
+            party_size: self.request.party_size,
+            at_time: self.request.at_time.clone(),
+        }))
+    }
+
+    /// Records what the user picked in the menu `next` last asked for.
+    pub fn pick(&mut self, values: &[String]) -> Result<(), String> {
+        if self.game_queue().is_none() {
+            let game_queue = values
+                .first()
+                .and_then(|game_name| self.find_game_queue(game_name))
+                .ok_or("Pick one of the games".to_string())?;
+            self.request.game = Some(game_queue.game_name.clone());
+            return Ok(());
+        }
+        if self.wait_secs.is_none() {
+            let wait_secs = values
+                .first()
+                .and_then(|wait_secs| wait_secs.parse().ok())
+                .ok_or("Pick one of the queue timers".to_string())?;
+            self.wait_secs = Some(wait_secs);
+            return Ok(());
+        }
+        self.roles = Some(
+            values
+                .iter()
+                .filter_map(|role_id| role_id.parse().ok().map(RoleId::new))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    fn game_queue(&self) -> Option<&'a GameQueues> {
+        self.find_game_queue(self.game_name.as_deref()?)
+    }
+
+    fn find_game_queue(&self, game_name: &str) -> Option<&'a GameQueues> {
+        self.game_queues
+            .iter()
+            .find(|game_queue| game_queue.game_name == game_name)
+    }
+
+    fn roles_for(&self, game_queue: &GameQueues) -> Vec<ReactionRole> {
+        self.game_roles
+            .get(&game_queue.game_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Reads the `in` option as a wait in seconds.
+fn parse_wait(wait: &str) -> Result<u64, String> {
+    let wait_secs = parse_duration(wait)
+        .ok_or(format!(
+            "Couldn't read \"{}\" as a time, try something like 10m or 1h30m",
+            wait
+        ))?
+        .as_secs();
+    if wait_secs > MAX_QUEUE_WAIT_SECS {
+        return Err(format!(
+            "Queues can only be set up to {} ahead, use at for later",
+            format_duration(Duration::from_secs(MAX_QUEUE_WAIT_SECS))
+        ));
+    }
+    Ok(wait_secs)
+}
+
+/// Reads the `at` option in the user's time zone, as a Unix timestamp.
+pub fn resolve_at_time(
+    at_time: &str,
+    time_zone: Option<TimeZone>,
+    now: u64,
+) -> Result<u64, String> {
+    let Some(time_zone) = time_zone else {
+        return Err("Set your time zone with /timezone first, so I know when that is".to_string());
+    };
+    resolve_clock_time(at_time, now, time_zone).ok_or(format!(
+        "Couldn't read \"{}\" as an upcoming time, try something like 9pm or tomorrow 20:30",
+        at_time
+    ))
+}
+
+/// Reads the `roles` option, which takes role mentions or role titles
+/// separated by commas, e.g. "@Theory Majors, casual candle trooper".
+pub fn parse_roles(roles: &str, game_roles: &[ReactionRole]) -> Result<Vec<RoleId>, String> {
+    let mut role_ids: Vec<RoleId> = vec![];
+    for role in roles
+        .split(',')
+        .map(str::trim)
+        .filter(|role| !role.is_empty())
+    {
+        let mentioned_roles: Option<Vec<RoleId>> =
+            role.split_whitespace().map(parse_role_mention).collect();
+        let found_roles = match mentioned_roles {
+            Some(mentioned_roles) => mentioned_roles
+                .into_iter()
+                .map(|role_id| {
+                    game_roles
+                        .iter()
+                        .find(|reaction_role| reaction_role.role_id == role_id)
+                        .ok_or(format!("<@&{}> isn't a role this game can ping", role_id))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![game_roles
+                .iter()
+                .find(|reaction_role| reaction_role.title.eq_ignore_ascii_case(role))
+                .ok_or(format!("Couldn't find a role called \"{}\"", role))?],
+        };
+        found_roles.into_iter().for_each(|reaction_role| {
+            if !role_ids.contains(&reaction_role.role_id) {
+                role_ids.push(reaction_role.role_id);
+            }
+        });
+    }
+
+    if role_ids.is_empty() {
+        Err("Pick at least one role to ping".to_string())
+    } else {
+        Ok(role_ids)
+    }
+}
+
+/// Whether the game can be queued for where and by whom the command was
+/// used, explaining why not if it can't.
+pub fn check_queue_allowed(
+    game_queue: &GameQueues,
+    member_roles: Option<&[RoleId]>,
+    channel_id: ChannelId,
+) -> Result<(), String> {
+    let Some(member_roles) = member_roles else {
+        return Err("Queues can only be started in a server".to_string());
+    };
+
+    let allowed_channels: Vec<ChannelId> = game_queue
+        .allowed_channels
+        .iter()
+        .copied()
+        .chain(game_queue.lfg_channel)
+        .collect();
+    if !allowed_channels.is_empty() && !allowed_channels.contains(&channel_id) {
+        let mut redirect = MessageBuilder::new();
+        redirect.push(format!(
+            "{} queues can't be started here, try",
+            game_queue.game_name
+        ));
+        game_queue
+            .lfg_channel
+            .map_or(allowed_channels.clone(), |lfg_channel| vec![lfg_channel])
+            .iter()
+            .for_each(|channel_id| {
+                redirect.push(" ").mention(channel_id);
+            });
+        return Err(redirect.build());
+    }
+
+    if let Some(required_role) = game_queue.required_role {
+        if !member_roles.contains(&required_role) {
+            return Err(MessageBuilder::new()
+                .push("You need the ")
+                .mention(&required_role)
+                .push(format!(" role to start {} queues", game_queue.game_name))
+                .build());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> u64 {
+            self.0
+        }
+    }
+
+    /// Stands in for the menus, answering each one the selection asks for
+    /// with the next picks, and timing out once there are none left.
+    struct FakePicks {
+        picks: Vec<Vec<&'static str>>,
+    }
+
+    impl FakePicks {
+        fn new(picks: Vec<Vec<&'static str>>) -> Self {
+            FakePicks { picks }
+        }
+
+        /// Answers menus until the selection is done or gives up. Returns
+        /// every menu asked, and the choices unless the user timed out.
+        fn play(
+            self,
+            selection: &mut QueueSelection<FixedClock>,
+        ) -> (Vec<SelectionMenu>, Result<Option<QueueChoices>, String>) {
+            let mut menus = vec![];
+            let mut picks = self.picks.into_iter();
+            loop {
+                let menu = match selection.next() {
+                    Ok(SelectionStep::Ask(menu)) => menu,
+                    Ok(SelectionStep::Done(choices)) => return (menus, Ok(Some(choices))),
+                    Err(why) => return (menus, Err(why)),
+                };
+                menus.push(menu);
+                let Some(values) = picks.next() else {
+                    return (menus, Ok(None));
+                };
+                let values: Vec<String> = values.into_iter().map(str::to_string).collect();
+                if let Err(why) = selection.pick(&values) {
+                    return (menus, Err(why));
+                }
+            }
+        }
+    }
+
+    fn game_queue(game_name: &str, extra: &str) -> GameQueues {
+        toml::from_str(&format!(
+            "game_name = \"{}\"\nroles_message_id = 1\nexclude = []\n{}",
+            game_name, extra
+        ))
+        .unwrap()
+    }
+
+    fn game_roles(game_queues: &[GameQueues]) -> HashMap<String, Vec<ReactionRole>> {
+        game_queues
+            .iter()
+            .map(|game_queue| {
+                (
+                    game_queue.game_name.clone(),
+                    vec![ReactionRole {
+                        emoji_id: None,
+                        emoji_char: Some("🕯️".to_string()),
+                        role_id: RoleId::new(11),
+                        title: "Casual".to_string(),
+                    }],
+                )
+            })
+            .collect()
+    }
+
+    fn request() -> QueueRequest {
+        QueueRequest {
+            channel_id: ChannelId::new(1),
+            member_roles: Some(vec![]),
+            ..Default::default()
+        }
+    }
+
+    fn choices(game_name: &str, wait_secs: u64) -> QueueChoices {
+        QueueChoices {
+            game_name: game_name.to_string(),
+            wait_secs,
+            roles: vec![RoleId::new(11)],
+            party_size: None,
+            at_time: None,
+        }
+    }
+
+    #[test]
+    fn asks_for_whatever_the_command_left_out() {
+        let game_queues = [game_queue("Deadlock", ""), game_queue("Dota", "")];
+        let game_roles = game_roles(&game_queues);
+        let mut selection =
+            QueueSelection::new(FixedClock(0), &game_queues, &game_roles, request());
+
+        let (menus, result) =
+            FakePicks::new(vec![vec!["Deadlock"], vec!["600"], vec!["11"]]).play(&mut selection);
+
+        assert_eq!(
+            menus,
+            vec![
+                SelectionMenu::Game { default_game: None },
+                SelectionMenu::Timer {
+                    game_name: "Deadlock".to_string(),
+                    default_wait_secs: None,
+                },
+                SelectionMenu::Roles {
+                    game_name: "Deadlock".to_string(),
+                    default_roles: vec![],
+                },
+            ]
+        );
+        assert_eq!(result, Ok(Some(choices("Deadlock", 600))));
+    }
+
+    #[test]
+    fn options_skip_the_menus() {
+        let game_queues = [game_queue("Deadlock", "")];
+        let game_roles = game_roles(&game_queues);
+        let mut selection = QueueSelection::new(
+            FixedClock(0),
+            &game_queues,
+            &game_roles,
+            QueueRequest {
+                wait: Some("10m".to_string()),
+                roles: Some("casual".to_string()),
+                ..request()
+            },
+        );
+
+        let (menus, result) = FakePicks::new(vec![]).play(&mut selection);
+
+        assert!(menus.is_empty());
+        assert_eq!(result, Ok(Some(choices("Deadlock", 600))));
+    }
+
+    #[test]
+    fn last_queue_is_only_the_default_for_the_same_game() {
+        let game_queues = [game_queue("Deadlock", ""), game_queue("Dota", "")];
+        let game_roles = game_roles(&game_queues);
+        let last_queue = Some(choices("Deadlock", 300));
+
+        let mut selection = QueueSelection::new(
+            FixedClock(0),
+            &game_queues,
+            &game_roles,
+            QueueRequest {
+                last_queue: last_queue.clone(),
+                ..request()
+            },
+        );
+        let (menus, _) = FakePicks::new(vec![vec!["Deadlock"], vec!["300"]]).play(&mut selection);
+        assert_eq!(
+            menus,
+            vec![
+                SelectionMenu::Game {
+                    default_game: Some("Deadlock".to_string()),
+                },
+                SelectionMenu::Timer {
+                    game_name: "Deadlock".to_string(),
+                    default_wait_secs: Some(300),
+                },
+                SelectionMenu::Roles {
+                    game_name: "Deadlock".to_string(),
+                    default_roles: vec![RoleId::new(11)],
+                },
+            ]
+        );
+
+        let mut selection = QueueSelection::new(
+            FixedClock(0),
+            &game_queues,
+            &game_roles,
+            QueueRequest {
+                game: Some("Dota".to_string()),
+                last_queue,
+                ..request()
+            },
+        );
+        let (menus, result) = FakePicks::new(vec![]).play(&mut selection);
+        assert_eq!(
+            menus,
+            vec![SelectionMenu::Timer {
+                game_name: "Dota".to_string(),
+                default_wait_secs: None,
+            }]
+        );
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn at_is_read_in_the_users_time_zone() {
+        let game_queues = [game_queue("Deadlock", "")];
+        let game_roles = game_roles(&game_queues);
+        let at_request = QueueRequest {
+            at_time: Some("9pm".to_string()),
+            roles: Some("casual".to_string()),
+            ..request()
+        };
+
+        let mut selection =
+            QueueSelection::new(FixedClock(0), &game_queues, &game_roles, at_request.clone());
+        let (_, result) = FakePicks::new(vec![]).play(&mut selection);
+        assert!(result.unwrap_err().contains("/timezone"));
+
+        let mut selection = QueueSelection::new(
+            FixedClock(0),
+            &game_queues,
+            &game_roles,
+            QueueRequest {
+                time_zone: Some(TimeZone::Fixed(-60 * 60)),
+                ..at_request
+            },
+        );
+        let (_, result) = FakePicks::new(vec![]).play(&mut selection);
+        let choices = result.unwrap().unwrap();
+        assert_eq!(choices.wait_secs, 22 * 60 * 60);
+        assert_eq!(choices.at_time.as_deref(), Some("9pm"));
+    }
+
+    #[test]
+    fn bad_options_stop_before_any_menu() {
+        let game_queues = [game_queue("Deadlock", "allowed_channels = [2]")];
+        let game_roles = game_roles(&game_queues);
+        let rejected = |request: QueueRequest| {
+            let mut selection =
+                QueueSelection::new(FixedClock(0), &game_queues, &game_roles, request);
+            let (menus, result) = FakePicks::new(vec![]).play(&mut selection);
+            assert!(menus.is_empty());
+            result.unwrap_err()
+        };
+
+        assert!(rejected(request()).contains("can't be started here"));
+        let allowed = QueueRequest {
+            channel_id: ChannelId::new(2),
+            ..request()
+        };
+        assert!(rejected(QueueRequest {
+            member_roles: None,
+            ..allowed.clone()
+        })
+        .contains("in a server"));
+        assert!(rejected(QueueRequest {
+            wait: Some("10m".to_string()),
+            at_time: Some("9pm".to_string()),
+            ..allowed.clone()
+        })
+        .contains("not both"));
+        assert!(rejected(QueueRequest {
+            wait: Some("99999h".to_string()),
+            ..allowed
+        })
+        .contains("use at for later"));
+    }
+}
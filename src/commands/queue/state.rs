@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueuePhase {
+    /// Counting down to the queue time, people can join the first game.
+    #[default]
+    Countdown,
+    /// The first game has started, people can line up for the next one.
+    Open,
+}
+
+/// Everything needed to pick a queue back up after a restart.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueueState {
+    pub game_name: String,
+    pub owner_id: UserId,
+    pub owner_name: String,
+    #[serde(default)]
+    pub owner_avatar_url: Option<String>,
+    #[serde(default)]
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub roles: Vec<RoleId>,
+    /// Roles listed on the queue without a ping because of a cooldown.
+    #[serde(default)]
+    pub muted_roles: Vec<RoleId>,
+    /// Unix timestamp of when the queue was posted.
+    pub started_at: u64,
+    /// Unix timestamp of when the countdown runs out, or when the latest
+    /// game started once the queue is open.
+    pub queue_at: u64,
+    /// Unix timestamp of roughly when the running game ends.
+    #[serde(default)]
+    pub match_at: u64,
+    #[serde(default)]
+    pub phase: QueuePhase,
+    #[serde(default)]
+    pub party_size: Option<usize>,
+    pub users_waiting: Vec<UserId>,
+    /// Players who joined after the party was already full, in join order.
+    #[serde(default)]
+    pub waitlist: Vec<UserId>,
+    /// Everyone who has joined at some point, for the queue history.
+    #[serde(default)]
+    pub joined_users: Vec<UserId>,
+    /// Unix timestamp of when the first game started.
+    #[serde(default)]
+    pub first_game_at: Option<u64>,
+    /// The voice channel claimed for the queue once its first game started.
+    #[serde(default)]
+    pub voice_channel_id: Option<ChannelId>,
+    /// What the voice channel was called before the queue renamed it, to
+    /// put back once the queue is over.
+    #[serde(default)]
+    pub voice_channel_name: Option<String>,
+    /// For a `[[scheduled_queue]]`, the start time it was posted for. It
+    /// stays put when the queue is extended or started early.
+    #[serde(default)]
+    pub scheduled_for: Option<u64>,
+    /// The discussion thread opened on the queue message.
+    #[serde(default)]
+    pub thread_id: Option<ChannelId>,
+    /// The Discord Scheduled Event created for a queue set far ahead.
+    #[serde(default)]
+    pub scheduled_event_id: Option<ScheduledEventId>,
+    /// The party of the game that started most recently, whose result gets
+    /// reported once it's over.
+    #[serde(default)]
+    pub last_party: Vec<UserId>,
+    /// Users already pulled in from the event's interested list, so leaving
+    /// the queue isn't undone by the next sync.
+    #[serde(default)]
+    pub event_users: Vec<UserId>,
+    /// Whether a ready check is waiting on answers. Checks don't survive a
+    /// restart, so this isn't saved.
+    #[serde(skip)]
+    pub ready_check_running: bool,
+}
+
+impl QueueState {
+    /// Adds or removes the user from the party, falling back to the waitlist
+    /// once the party is full. Returns true if this join filled the party.
+    pub fn toggle_user(&mut self, user_id: UserId) -> bool {
+        if !self.joined_users.contains(&user_id) {
+            self.joined_users.push(user_id);
+        }
+        if let Some(user_index) = self.users_waiting.iter().position(|id| *id == user_id) {
+            self.users_waiting.remove(user_index);
+            if !self.waitlist.is_empty() {
+                self.users_waiting.push(self.waitlist.remove(0));
+            }
+            false
+        } else if let Some(user_index) = self.waitlist.iter().position(|id| *id == user_id) {
+            self.waitlist.remove(user_index);
+            false
+        } else if self.is_full() {
+            self.waitlist.push(user_id);
+            false
+        } else {
+            self.users_waiting.push(user_id);
+            self.is_full()
+        }
+    }
+
+    /// When the current phase runs out.
+    pub fn wake_at(&self) -> u64 {
+        match self.phase {
+            QueuePhase::Countdown => self.queue_at,
+            QueuePhase::Open => self.match_at,
+        }
+    }
+
+    pub fn has_joined(&self, user_id: UserId) -> bool {
+        self.users_waiting.contains(&user_id) || self.waitlist.contains(&user_id)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.party_size
+            .is_some_and(|party_size| self.users_waiting.len() >= party_size)
+    }
+
+    /// Moves on to lining up the next game, returning the party that just
+    /// started. The waitlist gets first dibs on the next game.
+    pub fn start_next_game(&mut self, now: u64, match_length_secs: u64) -> Vec<UserId> {
+        self.queue_at = match self.phase {
+            QueuePhase::Countdown => self.queue_at.min(now),
+            QueuePhase::Open => now,
+        };
+        self.match_at = self.queue_at + match_length_secs;
+        self.first_game_at.get_or_insert(self.queue_at);
+        self.phase = QueuePhase::Open;
+        let party = std::mem::take(&mut self.users_waiting);
+        self.last_party = party.clone();
+        let waitlist = std::mem::take(&mut self.waitlist);
+        waitlist.into_iter().for_each(|user_id| {
+            self.toggle_user(user_id);
+        });
+        party
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use super::QueueCommand;
+use crate::clock_time::now_secs;
+use crate::settings::GameQueues;
+
+/// Who is playing which queue game according to their presence, and when
+/// each game was last suggested.
+#[derive(Default)]
+pub struct GameSightings {
+    playing: HashMap<GuildId, HashMap<UserId, String>>,
+    suggested_at: HashMap<(GuildId, String), u64>,
+}
+
+impl GameSightings {
+    /// Records what the user is playing now. Returns true if they just
+    /// started playing it.
+    pub fn update(&mut self, guild_id: GuildId, user_id: UserId, game_name: Option<&str>) -> bool {
+        let playing = self.playing.entry(guild_id).or_default();
+        match game_name {
+            Some(game_name) => {
+                playing.insert(user_id, game_name.to_string()).as_deref() != Some(game_name)
+            }
+            None => {
+                playing.remove(&user_id);
+                false
+            }
+        }
+    }
+
+    pub fn players(&self, guild_id: GuildId, game_name: &str) -> Vec<UserId> {
+        self.playing
+            .get(&guild_id)
+            .map(|playing| {
+                playing
+                    .iter()
+                    .filter(|(_, playing_game)| *playing_game == game_name)
+                    .map(|(user_id, _)| *user_id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the game's suggestion cooldown has run out.
+    pub fn suggestion_due(
+        &self,
+        guild_id: GuildId,
+        game_name: &str,
+        now: u64,
+        cooldown: Duration,
+    ) -> bool {
+        match self.suggested_at.get(&(guild_id, game_name.to_string())) {
+            Some(suggested_at) => now >= suggested_at + cooldown.as_secs(),
+            None => true,
+        }
+    }
+
+    /// Whether the game is due another suggestion, recording it if so.
+    pub fn take_suggestion(
+        &mut self,
+        guild_id: GuildId,
+        game_name: &str,
+        now: u64,
+        cooldown: Duration,
+    ) -> bool {
+        if !self.suggestion_due(guild_id, game_name, now, cooldown) {
+            return false;
+        }
+        self.suggested_at
+            .insert((guild_id, game_name.to_string()), now);
+        true
+    }
+}
+
+impl QueueCommand {
+    /// Watches for members starting a queue game, and suggests a queue once
+    /// enough of a game's LFG role are playing it at the same time.
+    pub async fn presence_update(&self, ctx: &Context, presence: Presence) {
+        let Some(guild_id) = presence.guild_id else {
+            return;
+        };
+        let user_id = presence.user.id;
+        let game_queue = presence
+            .activities
+            .iter()
+            .filter(|activity| activity.kind == ActivityType::Playing)
+            .find_map(|activity| self.activity_game_queue(&activity.name));
+        let started = self.game_sightings.lock().unwrap().update(
+            guild_id,
+            user_id,
+            game_queue.map(|game_queue| game_queue.game_name.as_str()),
+        );
+        let Some(game_queue) = game_queue else {
+            return;
+        };
+        // A queue that's already going is suggestion enough.
+        if !started
+            || self.queues.read(|queues| {
+                queues.values().any(|queue| {
+                    queue.guild_id == Some(guild_id) && queue.game_name == game_queue.game_name
+                })
+            })
+        {
+            return;
+        }
+
+        let players = {
+            let game_sightings = self.game_sightings.lock().unwrap();
+            // Checked before looking anyone up, so players joining in during
+            // the cooldown cost nothing.
+            if !game_sightings.suggestion_due(
+                guild_id,
+                &game_queue.game_name,
+                now_secs(),
+                game_queue.suggestion_cooldown,
+            ) {
+                return;
+            }
+            game_sightings.players(guild_id, &game_queue.game_name)
+        };
+        if players.len() < game_queue.suggest_at_players {
+            return;
+        }
+        let game_roles = self.game_queue_roles(game_queue);
+        let mut role_players: HashMap<RoleId, usize> = HashMap::new();
+        for player in players {
+            // Served from the cache when the member is in it.
+            let member = match guild_id.member(ctx, player).await {
+                Ok(member) => member,
+                Err(why) => {
+                    println!("Cannot look up the roles of {player}: {why}");
+                    continue;
+                }
+            };
+            game_roles
+                .iter()
+                .filter(|reaction_role| member.roles.contains(&reaction_role.role_id))
+                .for_each(|reaction_role| {
+                    *role_players.entry(reaction_role.role_id).or_default() += 1;
+                });
+        }
+        let Some((lfg_role, lfg_players)) = game_roles
+            .iter()
+            .filter_map(|reaction_role| {
+                Some((reaction_role, *role_players.get(&reaction_role.role_id)?))
+            })
+            .max_by_key(|(_, lfg_players)| *lfg_players)
+        else {
+            return;
+        };
+        if lfg_players < game_queue.suggest_at_players
+            || !self.game_sightings.lock().unwrap().take_suggestion(
+                guild_id,
+                &game_queue.game_name,
+                now_secs(),
+                game_queue.suggestion_cooldown,
+            )
+        {
+            return;
+        }
+
+        let suggestion = format!(
+            "{} {}s are in {} right now, start a `/queue`?",
+            lfg_players, lfg_role.title, game_queue.game_name
+        );
+        if let Some(lfg_channel) = game_queue.lfg_channel {
+            if let Err(why) = lfg_channel.say(ctx, &suggestion).await {
+                println!("Cannot post queue suggestion: {why}");
+            }
+        }
+        if game_queue.suggestion_dms {
+            self.suggest_by_dm(ctx, user_id, &suggestion).await;
+        }
+    }
+
+    async fn suggest_by_dm(&self, ctx: &Context, user_id: UserId, suggestion: &str) {
+        let no_queue_dms = self.user_settings.read(|user_settings| {
+            user_settings
+                .get(&user_id)
+                .is_some_and(|user_settings| user_settings.no_queue_dms)
+        });
+        if no_queue_dms {
+            return;
+        }
+        let dm = CreateMessage::new().content(suggestion);
+        let dm_result = match user_id.create_dm_channel(ctx).await {
+            Ok(dm_channel) => dm_channel.send_message(ctx, dm).await.map(|_| ()),
+            Err(why) => Err(why),
+        };
+        if let Err(why) = dm_result {
+            println!("Cannot DM {user_id} a queue suggestion: {why}");
+        }
+    }
+
+    /// The game queue a presence activity name belongs to.
+    fn activity_game_queue(&self, activity_name: &str) -> Option<&GameQueues> {
+        self.settings.game_queue.iter().find(|game_queue| {
+            game_queue
+                .activity_names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(activity_name))
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use serenity::builder::CreateEmbed;
+use serenity::model::prelude::*;
+use serenity::utils::MessageBuilder;
+
+/// Rating assumed for anyone who hasn't set one with `/queue skill`.
+const DEFAULT_SKILL_RATING: u32 = 5;
+
+pub type Teams = [Vec<UserId>; 2];
+
+pub fn random_teams(players: &[UserId]) -> Teams {
+    let mut players = players.to_vec();
+    players.shuffle(&mut rand::thread_rng());
+    let second_team = players.split_off(players.len().div_ceil(2));
+    [players, second_team]
+}
+
+/// Splits players so both teams end up with about the same total skill,
+/// keeping the team sizes within one of each other.
+pub fn balanced_teams(players: &[UserId], skill_ratings: &HashMap<UserId, u32>) -> Teams {
+    let mut players = players.to_vec();
+    players.shuffle(&mut rand::thread_rng());
+    players.sort_by_key(|user_id| std::cmp::Reverse(skill_rating(skill_ratings, user_id)));
+
+    let team_size = players.len().div_ceil(2);
+    let mut teams: Teams = [vec![], vec![]];
+    let mut totals = [0, 0];
+    players.into_iter().for_each(|user_id| {
+        let first_team_open = teams[0].len() < team_size;
+        let team_index =
+            if first_team_open && (teams[1].len() >= team_size || totals[0] <= totals[1]) {
+                0
+            } else {
+                1
+            };
+        totals[team_index] += skill_rating(skill_ratings, &user_id);
+        teams[team_index].push(user_id);
+    });
+    teams
+}
+
+pub fn build_teams_embed(
+    game_name: &str,
+    teams: &Teams,
+    skill_ratings: &HashMap<UserId, u32>,
+) -> CreateEmbed {
+    teams.iter().enumerate().fold(
+        CreateEmbed::new().title(format!("{} Teams", game_name)),
+        |embed, (team_index, team)| {
+            let mut roster = MessageBuilder::new();
+            team.iter().for_each(|user_id| {
+                roster.mention(user_id).push_line("");
+            });
+            let total_skill: u32 = team
+                .iter()
+                .map(|user_id| skill_rating(skill_ratings, user_id))
+                .sum();
+            embed.field(
+                format!("Team {} ({} skill)", team_index + 1, total_skill),
+                roster.build(),
+                true,
+            )
+        },
+    )
+}
+
+fn skill_rating(skill_ratings: &HashMap<UserId, u32>, user_id: &UserId) -> u32 {
+    skill_ratings
+        .get(user_id)
+        .copied()
+        .unwrap_or(DEFAULT_SKILL_RATING)
+}
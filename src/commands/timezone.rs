@@ -0,0 +1,80 @@
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::clock_time::{format_local_time, now_secs, TimeZone};
+use crate::user_settings::UserSettingsStore;
+
+pub struct TimezoneCommand {
+    user_settings: UserSettingsStore,
+}
+
+impl TimezoneCommand {
+    pub async fn run(
+        &self,
+        ctx: &Context,
+        timezone_command: &CommandInteraction,
+    ) -> Result<(), serenity::Error> {
+        let time_zone = timezone_command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "time_zone")
+            .and_then(|option| option.value.as_str())
+            .unwrap_or_default();
+
+        let content = match TimeZone::parse(time_zone) {
+            Some(time_zone) => {
+                self.user_settings.update(|user_settings| {
+                    user_settings
+                        .entry(timezone_command.user.id)
+                        .or_default()
+                        .time_zone = Some(time_zone.to_string());
+                });
+                let mut content = format!(
+                    "Your time zone is now {}, where it's {}",
+                    time_zone,
+                    format_local_time(now_secs(), time_zone)
+                );
+                if let TimeZone::Fixed(_) = time_zone {
+                    content.push_str(
+                        ". An offset stays put when your clocks change, \
+                        use a zone name like America/New_York to keep up with daylight saving",
+                    );
+                }
+                content
+            }
+            None => format!(
+                "Couldn't read \"{}\" as a time zone, try a name like America/New_York or an offset like -05:00",
+                time_zone
+            ),
+        };
+        timezone_command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(content),
+                ),
+            )
+            .await
+    }
+
+    pub fn new(user_settings: UserSettingsStore) -> TimezoneCommand {
+        TimezoneCommand { user_settings }
+    }
+
+    pub fn register(&self) -> CreateCommand {
+        CreateCommand::new("timezone")
+            .description("Set the time zone used when you queue for a clock time")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "time_zone",
+                    "Your time zone, like America/New_York, or an offset from UTC like -05:00",
+                )
+                .required(true),
+            )
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+/// Parses human durations like "30s", "10m", "1h30m" or "1 hour 30 mins".
+/// A bare number is taken as minutes.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    if let Ok(minutes) = input.parse::<u64>() {
+        return Some(Duration::from_secs(minutes.checked_mul(60)?));
+    }
+
+    let mut total_secs: u64 = 0;
+    let mut chars = input.chars().filter(|c| !c.is_whitespace()).peekable();
+    while chars.peek().is_some() {
+        let amount: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit())).collect();
+        let unit: String = std::iter::from_fn(|| chars.next_if(|c| c.is_alphabetic())).collect();
+        let amount: u64 = amount.parse().ok()?;
+        let unit_secs = match unit.as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            _ => return None,
+        };
+        total_secs = total_secs.checked_add(amount.checked_mul(unit_secs)?)?;
+    }
+
+    if total_secs == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(total_secs))
+    }
+}
+
+/// Formats a duration the way the queue menus show it, e.g. "1 Hour 30 Minutes".
+pub fn format_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    let parts: Vec<String> = [
+        (total_secs / (60 * 60), "Hour"),
+        ((total_secs / 60) % 60, "Minute"),
+        (total_secs % 60, "Second"),
+    ]
+    .iter()
+    .filter(|(amount, _)| *amount > 0)
+    .map(|(amount, unit)| {
+        if *amount == 1 {
+            format!("{} {}", amount, unit)
+        } else {
+            format!("{} {}s", amount, unit)
+        }
+    })
+    .collect();
+    parts.join(" ")
+}
+
+/// For settings fields written as duration strings, e.g. `match_length = "40m"`.
+pub fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let duration = String::deserialize(deserializer)?;
+    parse_duration(&duration)
+        .ok_or_else(|| D::Error::custom(format!("invalid duration \"{}\"", duration)))
+}
+
+pub fn deserialize_durations<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Duration>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|duration| {
+            parse_duration(duration)
+                .ok_or_else(|| D::Error::custom(format!("invalid duration \"{}\"", duration)))
+        })
+        .collect()
+}
+
+pub fn deserialize_optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
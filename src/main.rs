@@ -1,24 +1,33 @@
+mod clock_time;
 mod commands;
+mod duration;
+mod match_results;
 mod reaction_roles;
 mod settings;
+mod store;
+mod user_settings;
+mod voice_expander;
 
 use std::env;
 
+use commands::leaderboard::LeaderboardCommand;
+use commands::notifications::NotificationsCommand;
 use commands::queue::QueueCommand;
+use commands::timezone::TimezoneCommand;
 use dotenv::dotenv;
-use rand::seq::SliceRandom;
-use serenity::builder::{
-    CreateChannel, CreateInteractionResponse, CreateInteractionResponseMessage,
-};
+use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage};
 use serenity::futures::future::join_all;
 use serenity::http::Http;
-use serenity::model::{guild, prelude::*};
+use serenity::model::prelude::*;
 use serenity::{async_trait, prelude::*};
 use settings::Settings;
 
 struct Handler {
     settings: Settings,
     queue_command: QueueCommand,
+    notifications_command: NotificationsCommand,
+    timezone_command: TimezoneCommand,
+    leaderboard_command: LeaderboardCommand,
 }
 
 enum CollectorEvent {
@@ -29,10 +38,22 @@ enum CollectorEvent {
 impl Handler {
     fn new() -> Self {
         let settings = Settings::deserialize().expect("Should match the config format");
-        let queue_command = QueueCommand::new(settings.clone());
+        let user_settings = user_settings::open_user_settings();
+        let match_results = match_results::open_match_results();
+        let queue_command = QueueCommand::new(
+            settings.clone(),
+            user_settings.clone(),
+            match_results.clone(),
+        );
+        let notifications_command = NotificationsCommand::new(user_settings.clone());
+        let timezone_command = TimezoneCommand::new(user_settings);
+        let leaderboard_command = LeaderboardCommand::new(settings.clone(), match_results);
         Handler {
             settings,
             queue_command,
+            notifications_command,
+            timezone_command,
+            leaderboard_command,
         }
     }
 }
@@ -43,7 +64,27 @@ impl EventHandler for Handler {
         if let Interaction::Command(command) = interaction {
             let content = match command.data.name.as_str() {
                 "queue" => {
-                    self.queue_command.run(&ctx, &command).await.unwrap();
+                    if let Err(why) = self.queue_command.run(&ctx, &command).await {
+                        println!("Queue command failed: {why}");
+                    }
+                    None
+                }
+                "notifications" => {
+                    if let Err(why) = self.notifications_command.run(&ctx, &command).await {
+                        println!("Notifications command failed: {why}");
+                    }
+                    None
+                }
+                "timezone" => {
+                    if let Err(why) = self.timezone_command.run(&ctx, &command).await {
+                        println!("Timezone command failed: {why}");
+                    }
+                    None
+                }
+                "leaderboard" => {
+                    if let Err(why) = self.leaderboard_command.run(&ctx, &command).await {
+                        println!("Leaderboard command failed: {why}");
+                    }
                     None
                 }
                 _ => Some("not implemented :(".to_string()),
@@ -59,7 +100,26 @@ impl EventHandler for Handler {
         };
     }
 
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        self.queue_command
+            .message_deleted(&ctx, deleted_message_id)
+            .await;
+    }
+
+    async fn presence_update(&self, ctx: Context, new_data: Presence) {
+        self.queue_command.presence_update(&ctx, new_data).await;
+    }
+
     async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
+        self.queue_command
+            .voice_state_update(&ctx, old.as_ref(), &new)
+            .await;
         if let Some(channel_id) = new.channel_id {
             if let Ok(channel) = ctx.http.get_channel(channel_id).await {
                 if let Some(channel) = channel.guild() {
@@ -67,54 +127,37 @@ impl EventHandler for Handler {
                         if let Some(category_settings) =
                             self.settings.voice_expander.get(&parent_id)
                         {
-                            let voice_channels: Vec<GuildChannel> = ctx
-                                .http
-                                .get_channels(channel.guild_id)
-                                .await
-                                .unwrap()
-                                .iter()
-                                .filter_map(|ch| {
-                                    if ch.parent_id == Some(parent_id)
-                                        && ch.kind == ChannelType::Voice
-                                    {
-                                        Some(ch.clone())
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect();
+                            let voice_channels = voice_expander::category_voice_channels(
+                                &ctx,
+                                channel.guild_id,
+                                parent_id,
+                            )
+                            .await;
                             if voice_channels.len() < category_settings.max_channels {
                                 let current_names: Vec<String> =
                                     voice_channels.iter().map(|ch| ch.name.clone()).collect();
-                                let name_options: Vec<String> = category_settings
-                                    .channel_names
+                                // A channel reserved by a queue isn't free for anyone else.
+                                let queue_voice_channels =
+                                    self.queue_command.queue_voice_channels();
+                                let number_of_empty_channels = voice_channels
                                     .iter()
-                                    .filter(|chn| !current_names.contains(chn))
-                                    .map(|name| name.clone())
-                                    .collect();
-                                if let Ok(guild) = ctx.http.get_guild(channel.guild_id).await {
-                                    let number_of_empty_channels = voice_channels
-                                        .iter()
-                                        .filter(|ch| ch.members(&ctx).unwrap_or(vec![]).len() == 0)
-                                        .count();
-                                    if number_of_empty_channels == 0 {
-                                        let channel_name = name_options
-                                            .choose(&mut rand::thread_rng())
-                                            .unwrap_or(&"ERROR".to_string())
-                                            .clone();
-                                        guild
-                                            .create_channel(
-                                                &ctx,
-                                                CreateChannel::new(channel_name)
-                                                    .kind(ChannelType::Voice)
-                                                    .category(parent_id)
-                                                    .audit_log_reason(
-                                                        "Create a New Empty Voice Channel",
-                                                    ),
-                                            )
-                                            .await
-                                            .unwrap();
-                                    }
+                                    .filter(|ch| {
+                                        ch.members(&ctx).unwrap_or(vec![]).is_empty()
+                                            && !queue_voice_channels.contains(&ch.id)
+                                    })
+                                    .count();
+                                if number_of_empty_channels == 0 {
+                                    voice_expander::create_voice_channel(
+                                        &ctx,
+                                        channel.guild_id,
+                                        parent_id,
+                                        voice_expander::unused_channel_name(
+                                            category_settings,
+                                            &current_names,
+                                        ),
+                                    )
+                                    .await
+                                    .unwrap();
                                 }
                             }
                         }
@@ -139,6 +182,7 @@ impl EventHandler for Handler {
                     })
                     .collect();
 
+                let queue_voice_channels = self.queue_command.queue_voice_channels();
                 let mut channels_to_delete = vec![];
                 self.settings.voice_expander.keys().for_each(|category_id| {
                     let mut voice_channel_deletes: Vec<_> = voice_channels
@@ -146,7 +190,8 @@ impl EventHandler for Handler {
                         .rev()
                         .filter(|ch| {
                             ch.parent_id == Some(*category_id)
-                                && ch.members(&ctx).unwrap_or(vec![]).len() == 0
+                                && ch.members(&ctx).unwrap_or(vec![]).is_empty()
+                                && !queue_voice_channels.contains(&ch.id)
                         })
                         .skip(1)
                         .map(|vch| vch.delete(&ctx))
@@ -165,6 +210,17 @@ impl EventHandler for Handler {
             Command::create_global_command(&ctx.http, self.queue_command.register())
                 .await
                 .expect("Failed to Register Command");
+            Command::create_global_command(&ctx.http, self.notifications_command.register())
+                .await
+                .expect("Failed to Register Command");
+            Command::create_global_command(&ctx.http, self.timezone_command.register())
+                .await
+                .expect("Failed to Register Command");
+            Command::create_global_command(&ctx.http, self.leaderboard_command.register())
+                .await
+                .expect("Failed to Register Command");
+            self.queue_command.resume(&ctx).await;
+            self.queue_command.start_scheduler(&ctx);
         }
 
         reaction_roles::ReactionRole::register(&ctx, &self.settings).await;
@@ -185,16 +241,27 @@ async fn main() {
         Err(why) => panic!("Could not access user info: {:?}", why),
     };
 
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let handler = Handler::new();
+    let mut intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::GUILDS
         | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT
         | GatewayIntents::GUILD_MESSAGE_REACTIONS
         | GatewayIntents::AUTO_MODERATION_CONFIGURATION;
+    // Presences are privileged, and only needed to suggest queues for games
+    // with activity names to look out for.
+    if handler
+        .settings
+        .game_queue
+        .iter()
+        .any(|game_queue| !game_queue.activity_names.is_empty())
+    {
+        intents |= GatewayIntents::GUILD_PRESENCES;
+    }
 
     let mut client = Client::builder(&token, intents)
-        .event_handler(Handler::new())
+        .event_handler(handler)
         .await
         .expect("Err creating client");
 
@@ -0,0 +1,80 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+
+use crate::store::Store;
+
+/// How a game played out of a queue went, as reported by the queue owner.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchResult {
+    pub game_name: String,
+    pub guild_id: Option<GuildId>,
+    pub players: Vec<UserId>,
+    pub won: bool,
+    #[serde(default)]
+    pub mvps: Vec<UserId>,
+    /// Unix timestamp of when the result was reported.
+    pub reported_at: u64,
+}
+
+pub type MatchResultsStore = Store<Vec<MatchResult>>;
+
+pub fn open_match_results() -> MatchResultsStore {
+    Store::open("match_results.json")
+}
+
+/// One player's results in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerRecord {
+    pub user_id: UserId,
+    pub games: usize,
+    pub wins: usize,
+    pub mvps: usize,
+}
+
+impl PlayerRecord {
+    pub fn win_rate_percent(&self) -> usize {
+        self.wins * 100 / self.games.max(1)
+    }
+
+    /// Higher win rates first, with more games played breaking ties.
+    fn rank(&self, other: &PlayerRecord) -> Ordering {
+        (other.wins * self.games)
+            .cmp(&(self.wins * other.games))
+            .then(other.games.cmp(&self.games))
+    }
+}
+
+/// Tallies up everyone's results in a game on one server, best first.
+pub fn player_records(
+    results: &[MatchResult],
+    game_name: &str,
+    guild_id: GuildId,
+) -> Vec<PlayerRecord> {
+    let mut records: HashMap<UserId, PlayerRecord> = HashMap::new();
+    results
+        .iter()
+        .filter(|result| result.game_name == game_name && result.guild_id == Some(guild_id))
+        .for_each(|result| {
+            result.players.iter().for_each(|user_id| {
+                let record = records.entry(*user_id).or_insert(PlayerRecord {
+                    user_id: *user_id,
+                    games: 0,
+                    wins: 0,
+                    mvps: 0,
+                });
+                record.games += 1;
+                if result.won {
+                    record.wins += 1;
+                }
+                if result.mvps.contains(user_id) {
+                    record.mvps += 1;
+                }
+            });
+        });
+    let mut records: Vec<PlayerRecord> = records.into_values().collect();
+    records.sort_by(|record, other| record.rank(other));
+    records
+}
@@ -1,9 +1,13 @@
+use std::time::Duration;
 use std::{collections::HashMap, env, fs};
 
+use serde::de::Error as _;
 use serde::Deserialize;
 use serenity::model::prelude::*;
 use toml::de::Error;
 
+use crate::clock_time::{parse_weekday, resolve_clock_time, TimeZone};
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ReactionRole {
     pub emoji_id: Option<EmojiId>,
@@ -19,11 +23,229 @@ pub struct ReactionRoles {
     pub roles: HashMap<String, ReactionRole>,
 }
 
+/// What to call the voice channel a queue gets once its first game starts.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueVoiceChannelName {
+    Owner,
+    Game,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct GameQueues {
     pub game_name: String,
     pub roles_message_id: MessageId,
     pub exclude: Vec<String>,
+    #[serde(default)]
+    pub party_size: Option<usize>,
+    /// The choices offered in the queue timer menu.
+    #[serde(
+        default = "GameQueues::default_wait_times",
+        deserialize_with = "crate::duration::deserialize_durations"
+    )]
+    pub wait_times: Vec<Duration>,
+    /// Roughly how long one match takes, used for the "Approx. Next Match" time
+    /// until enough games have been timed in the queues' voice channels.
+    #[serde(
+        default = "GameQueues::default_match_length",
+        deserialize_with = "crate::duration::deserialize_duration"
+    )]
+    pub match_length: Duration,
+    /// Set to give each queue its own voice channel from the voice expander.
+    #[serde(default)]
+    pub voice_channel: Option<QueueVoiceChannelName>,
+    /// Open a thread on each queue message for the group to talk in.
+    #[serde(default)]
+    pub discussion_thread: bool,
+    /// Queues set this far out or further also get a Discord Scheduled Event,
+    /// so people can mark themselves interested ahead of time.
+    #[serde(
+        default,
+        deserialize_with = "crate::duration::deserialize_optional_duration"
+    )]
+    pub scheduled_event_after: Option<Duration>,
+    /// How long people get to answer a ready check.
+    #[serde(
+        default = "GameQueues::default_ready_check_timeout",
+        deserialize_with = "crate::duration::deserialize_duration"
+    )]
+    pub ready_check_timeout: Duration,
+    /// Channels queues can be started in. Left empty along with
+    /// `lfg_channel`, queues can go anywhere.
+    #[serde(default)]
+    pub allowed_channels: Vec<ChannelId>,
+    /// Where people trying to queue somewhere else get sent.
+    #[serde(default)]
+    pub lfg_channel: Option<ChannelId>,
+    /// A role members need before they can start a queue.
+    #[serde(default)]
+    pub required_role: Option<RoleId>,
+    /// Presence activity names that count as playing the game. Left empty,
+    /// the game never gets queue suggestions.
+    #[serde(default)]
+    pub activity_names: Vec<String>,
+    /// How many members of one of the game's roles have to be playing at once
+    /// before a queue is suggested in the `lfg_channel`.
+    #[serde(default = "GameQueues::default_suggest_at_players")]
+    pub suggest_at_players: usize,
+    /// How long before the game can be suggested again.
+    #[serde(
+        default = "GameQueues::default_suggestion_cooldown",
+        deserialize_with = "crate::duration::deserialize_duration"
+    )]
+    pub suggestion_cooldown: Duration,
+    /// Also DM the suggestion to whoever just started playing, unless they
+    /// turned queue DMs off.
+    #[serde(default)]
+    pub suggestion_dms: bool,
+}
+
+impl GameQueues {
+    fn default_wait_times() -> Vec<Duration> {
+        [30, 5 * 60, 10 * 60, 15 * 60, 30 * 60]
+            .into_iter()
+            .map(Duration::from_secs)
+            .collect()
+    }
+
+    pub fn default_match_length() -> Duration {
+        Duration::from_secs(40 * 60)
+    }
+
+    pub fn default_ready_check_timeout() -> Duration {
+        Duration::from_secs(2 * 60)
+    }
+
+    fn default_suggest_at_players() -> usize {
+        3
+    }
+
+    fn default_suggestion_cooldown() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.wait_times.is_empty() || self.wait_times.len() > 25 {
+            return Err(format!(
+                "{} needs between 1 and 25 wait_times",
+                self.game_name
+            ));
+        }
+        if let Some(wait_time) = self
+            .wait_times
+            .iter()
+            .find(|wait_time| **wait_time >= self.match_length)
+        {
+            return Err(format!(
+                "{} wait time of {}s must be shorter than its match_length",
+                self.game_name,
+                wait_time.as_secs()
+            ));
+        }
+        if self.suggest_at_players == 0 {
+            return Err(format!(
+                "{} needs a suggest_at_players of at least 1",
+                self.game_name
+            ));
+        }
+        // Nobody could ever get into a party of 0.
+        if self.party_size == Some(0) {
+            return Err(format!(
+                "{} needs a party_size of at least 1",
+                self.game_name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A queue posted automatically every week, like a regular game night.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScheduledQueue {
+    pub game_name: String,
+    pub channel_id: ChannelId,
+    pub roles: Vec<RoleId>,
+    /// Weekdays the queue runs on, e.g. `["friday"]` or `["mon", "wed"]`.
+    pub days: Vec<String>,
+    /// Clock time the queue starts at, e.g. "20:00" or "8pm".
+    pub time: String,
+    /// The time zone `time` is in, e.g. "America/New_York". A fixed offset
+    /// like "-05:00" works too, but won't follow daylight saving. Defaults
+    /// to UTC.
+    #[serde(default)]
+    pub time_zone: String,
+    /// How long before the start the queue gets posted.
+    #[serde(
+        default = "ScheduledQueue::default_post_before",
+        deserialize_with = "crate::duration::deserialize_duration"
+    )]
+    pub post_before: Duration,
+    /// Who gets the owner buttons. Left out, the bot runs the queue itself.
+    #[serde(default)]
+    pub owner_id: Option<UserId>,
+    #[serde(default)]
+    pub party_size: Option<usize>,
+}
+
+impl ScheduledQueue {
+    fn default_post_before() -> Duration {
+        Duration::from_secs(30 * 60)
+    }
+
+    /// The next time this queue starts after `now`.
+    pub fn next_start(&self, now: u64) -> Option<u64> {
+        let time_zone = TimeZone::parse(&self.time_zone)?;
+        self.days
+            .iter()
+            .filter_map(|day| resolve_clock_time(&format!("{} {}", day, self.time), now, time_zone))
+            .min()
+    }
+
+    fn validate(&self, game_queues: &[GameQueues]) -> Result<(), String> {
+        if !game_queues
+            .iter()
+            .any(|game_queue| game_queue.game_name == self.game_name)
+        {
+            return Err(format!(
+                "scheduled_queue game {} has no game_queue",
+                self.game_name
+            ));
+        }
+        if self.party_size == Some(0) {
+            return Err(format!(
+                "scheduled_queue for {} needs a party_size of at least 1",
+                self.game_name
+            ));
+        }
+        if TimeZone::parse(&self.time_zone).is_none() {
+            return Err(format!("invalid time_zone \"{}\"", self.time_zone));
+        }
+        if self.days.is_empty() {
+            return Err(format!("scheduled_queue for {} needs days", self.game_name));
+        }
+        if let Some(day) = self.days.iter().find(|day| parse_weekday(day).is_none()) {
+            return Err(format!("invalid scheduled_queue day \"{}\"", day));
+        }
+        if self.next_start(0).is_none() {
+            return Err(format!("invalid scheduled_queue time \"{}\"", self.time));
+        }
+        Ok(())
+    }
+}
+
+/// How long before the same user, role or channel can be pinged by another
+/// queue. Anything left out has no cooldown.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct QueueCooldowns {
+    #[serde(default, deserialize_with = "crate::duration::deserialize_duration")]
+    pub user: Duration,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_duration")]
+    pub role: Duration,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_duration")]
+    pub channel: Duration,
+    /// Members with any of these roles skip the cooldowns.
+    #[serde(default)]
+    pub moderator_roles: Vec<RoleId>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -48,6 +270,10 @@ pub struct Settings {
     pub reaction_roles: Vec<ReactionRoles>,
     pub flags: Flags,
     pub game_queue: Vec<GameQueues>,
+    #[serde(default)]
+    pub queue_cooldowns: QueueCooldowns,
+    #[serde(default)]
+    pub scheduled_queue: Vec<ScheduledQueue>,
     pub voice_expander: HashMap<ChannelId, VoiceExpanderSetting>,
 }
 
@@ -58,7 +284,15 @@ impl Settings {
         )
         .unwrap();
         let reactions_roles: Settings = toml::from_str(file_str.as_str())?;
-        return Ok(reactions_roles);
+        for game_queue in &reactions_roles.game_queue {
+            game_queue.validate().map_err(Error::custom)?;
+        }
+        for scheduled_queue in &reactions_roles.scheduled_queue {
+            scheduled_queue
+                .validate(&reactions_roles.game_queue)
+                .map_err(Error::custom)?;
+        }
+        Ok(reactions_roles)
     }
 
     pub fn message_id_to_channel_id(&self) -> HashMap<MessageId, ChannelId> {
@@ -80,9 +314,9 @@ impl Settings {
         > = HashMap::new();
         for reaction_role_message in self.reaction_roles.clone() {
             let message_id = reaction_role_message.message_id;
-            if !message_id_to_emoji_reaction_to_role_lookup.contains_key(&message_id) {
-                message_id_to_emoji_reaction_to_role_lookup.insert(message_id, HashMap::new());
-            }
+            message_id_to_emoji_reaction_to_role_lookup
+                .entry(message_id)
+                .or_default();
 
             for reaction_name in reaction_role_message.roles.keys() {
                 let reaction_role = reaction_role_message.roles.get(reaction_name).unwrap();
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{env, fs};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A value kept in memory and mirrored to a JSON file in the data directory,
+/// so it outlives a restart of the bot.
+pub struct Store<T> {
+    path: PathBuf,
+    value: Arc<Mutex<T>>,
+}
+
+impl<T> Clone for Store<T> {
+    fn clone(&self) -> Self {
+        Store {
+            path: self.path.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Default> Store<T> {
+    pub fn open(file_name: &str) -> Self {
+        let path =
+            PathBuf::from(env::var("DATA_PATH").unwrap_or("data".to_string())).join(file_name);
+        let value = match fs::read_to_string(&path) {
+            Ok(file_str) => serde_json::from_str(&file_str).unwrap_or_else(|why| {
+                println!("Could not read {}, starting empty: {why}", path.display());
+                T::default()
+            }),
+            Err(_) => T::default(),
+        };
+        Store {
+            path,
+            value: Arc::new(Mutex::new(value)),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.value.lock().unwrap())
+    }
+
+    /// Changes the value and writes it back to disk before returning.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.value.lock().unwrap();
+        let result = f(&mut value);
+        if let Err(why) = self.save(&value) {
+            println!("Could not save {}: {why}", self.path.display());
+        }
+        result
+    }
+
+    fn save(&self, value: &T) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
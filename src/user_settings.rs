@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+
+use crate::clock_time::TimeZone;
+use crate::store::Store;
+
+/// Preferences each member sets for themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UserSettings {
+    /// Ping in the queue channel instead of sending a DM when a game is starting.
+    #[serde(default)]
+    pub no_queue_dms: bool,
+    /// Self-reported skill from 1 to 10, used to balance teams.
+    #[serde(default)]
+    pub skill_rating: Option<u32>,
+    /// Time zone name or UTC offset, set with `/timezone`.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// What the user picked for their last queue, for `/queue again`.
+    #[serde(default)]
+    pub last_queue: Option<QueueChoices>,
+}
+
+impl UserSettings {
+    /// The time zone the user's clock times are read in, if they've set one.
+    pub fn time_zone(&self) -> Option<TimeZone> {
+        self.time_zone.as_deref().and_then(TimeZone::parse)
+    }
+}
+
+/// The choices that make up a queue before it's posted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueueChoices {
+    pub game_name: String,
+    pub wait_secs: u64,
+    pub roles: Vec<RoleId>,
+    /// The party size picked over the game's usual one, if any.
+    #[serde(default)]
+    pub party_size: Option<usize>,
+    /// The clock time picked with `at`, if any. `/queue again` reads it
+    /// afresh, since the wait only made sense from when it was picked.
+    #[serde(default)]
+    pub at_time: Option<String>,
+}
+
+pub type UserSettingsStore = Store<HashMap<UserId, UserSettings>>;
+
+pub fn open_user_settings() -> UserSettingsStore {
+    Store::open("user_settings.json")
+}
@@ -0,0 +1,102 @@
+use rand::seq::SliceRandom;
+use serenity::builder::CreateChannel;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::settings::{Settings, VoiceExpanderSetting};
+
+/// The voice expander category set up in this guild, if there is one.
+pub async fn guild_category(
+    ctx: &Context,
+    settings: &Settings,
+    guild_id: GuildId,
+) -> Option<(ChannelId, VoiceExpanderSetting)> {
+    let channels = guild_id.channels(ctx).await.ok()?;
+    settings
+        .voice_expander
+        .iter()
+        .find(|(category_id, _)| channels.contains_key(category_id))
+        .map(|(category_id, category_settings)| (*category_id, category_settings.clone()))
+}
+
+pub async fn category_voice_channels(
+    ctx: &Context,
+    guild_id: GuildId,
+    category_id: ChannelId,
+) -> Vec<GuildChannel> {
+    ctx.http
+        .get_channels(guild_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|ch| ch.parent_id == Some(category_id) && ch.kind == ChannelType::Voice)
+        .collect()
+}
+
+/// Finds `count` empty voice channels in the category, creating new ones as
+/// long as the category has room for them. Channels in `reserved` are taken
+/// even while empty.
+pub async fn claim_empty_channels(
+    ctx: &Context,
+    guild_id: GuildId,
+    category_id: ChannelId,
+    category_settings: &VoiceExpanderSetting,
+    reserved: &[ChannelId],
+    count: usize,
+) -> Vec<GuildChannel> {
+    let voice_channels = category_voice_channels(ctx, guild_id, category_id).await;
+    let mut channel_names: Vec<String> = voice_channels.iter().map(|ch| ch.name.clone()).collect();
+    let mut claimed_channels: Vec<GuildChannel> = voice_channels
+        .iter()
+        .filter(|ch| !reserved.contains(&ch.id) && ch.members(ctx).unwrap_or(vec![]).is_empty())
+        .take(count)
+        .cloned()
+        .collect();
+
+    let mut channel_count = voice_channels.len();
+    while claimed_channels.len() < count && channel_count < category_settings.max_channels {
+        let channel_name = unused_channel_name(category_settings, &channel_names);
+        match create_voice_channel(ctx, guild_id, category_id, channel_name.clone()).await {
+            Ok(channel) => claimed_channels.push(channel),
+            Err(why) => {
+                println!("Cannot create voice channel: {why}");
+                break;
+            }
+        }
+        channel_names.push(channel_name);
+        channel_count += 1;
+    }
+    claimed_channels
+}
+
+pub async fn create_voice_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    category_id: ChannelId,
+    channel_name: String,
+) -> Result<GuildChannel, serenity::Error> {
+    guild_id
+        .create_channel(
+            ctx,
+            CreateChannel::new(channel_name)
+                .kind(ChannelType::Voice)
+                .category(category_id)
+                .audit_log_reason("Create a New Empty Voice Channel"),
+        )
+        .await
+}
+
+pub fn unused_channel_name(
+    category_settings: &VoiceExpanderSetting,
+    current_names: &[String],
+) -> String {
+    let name_options: Vec<&String> = category_settings
+        .channel_names
+        .iter()
+        .filter(|chn| !current_names.contains(chn))
+        .collect();
+    name_options
+        .choose(&mut rand::thread_rng())
+        .map(|name| name.to_string())
+        .unwrap_or("ERROR".to_string())
+}